@@ -0,0 +1,124 @@
+/*
+ * the apply subcommand: run the files of a split directory against a
+ * database, in an order they can be created in
+ */
+use crate::cli::Cli;
+use crate::dependencies::BUILD_ORDER_FILE;
+use crate::diagnostics::Diagnostics;
+use crate::error::{ IoContext, SplitError };
+use crate::object::{ Layout, ObjectType, deploy_rank };
+use crate::output::STAGING_DIR;
+use crate::server::Connection;
+use clap::ValueEnum;
+use std::fs;
+use std::path::Path;
+
+/// the order files are run in
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ApplyOrder {
+    // by object type (schemas before tables before views, ...), then by path
+    Type,
+    // as listed in build-order.txt (see --build-order)
+    BuildOrder,
+}
+
+/// the paths (relative to `root`, with `/` separators) of the `.sql` files
+/// in the subdirectories of `dir`
+fn object_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> Result<(), SplitError> {
+    let display = dir.display().to_string();
+    for entry in fs::read_dir(dir).create_context(&display)? {
+        let path = entry.create_context(&display)?.path();
+        if path.is_dir() {
+            // a split into `dir` that is still running (or was killed)
+            if path.file_name().is_some_and(|name| name == STAGING_DIR) {
+                continue;
+            }
+            object_files(root, &path, files)?;
+        } else if dir != root && path.extension().is_some_and(|e| e == "sql") {
+            let relative: Vec<String> = path.strip_prefix(root)
+                .unwrap_or(&path)
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            files.push(relative.join("/"));
+        }
+    }
+    Ok(())
+}
+
+/// the deployment rank of the type whose folder (in either layout) `path` is
+/// in, possibly inside a database's folder; a table's parts (--table-parts)
+/// go by their own ranks
+fn rank(path: &str) -> usize {
+    if path.ends_with(".constraints.sql") {
+        return deploy_rank("Constraints");
+    } else if path.ends_with(".indexes.sql") {
+        return deploy_rank("Indexes");
+    }
+    let folder = path.rsplit_once('/').map(|(folder, _)| folder).unwrap_or_default();
+    ObjectType::ALL.iter()
+        .find(|object_type| [Layout::Types, Layout::Redgate].iter().any(|&layout| {
+            let type_folder = object_type.folder_in(layout);
+            folder == type_folder || folder.ends_with(&format!("/{}", type_folder))
+        }))
+        .map_or(deploy_rank(""), |object_type| deploy_rank(&object_type.to_string()))
+}
+
+/// the files of `dir` to run, in `order`
+fn files(dir: &str, order: ApplyOrder) -> Result<Vec<String>, SplitError> {
+    match order {
+        ApplyOrder::Type => {
+            let mut files = Vec::new();
+            object_files(Path::new(dir), Path::new(dir), &mut files)?;
+            files.sort_by_cached_key(|path| (rank(path), path.to_owned()));
+            Ok(files)
+        },
+        ApplyOrder::BuildOrder => {
+            let path = Path::new(dir).join(BUILD_ORDER_FILE);
+            let text = fs::read_to_string(&path)
+                .map_err(|source| SplitError::OpenInput { path: path.display().to_string(), source })?;
+            Ok(text.lines().filter(|line| !line.is_empty()).map(str::to_string).collect())
+        },
+    }
+}
+
+/// run the files of `dir` against the database of `connection`, reporting
+/// each one on stdout; stops at the first failure unless `keep_going`
+pub fn apply(
+    cli:         &Cli,
+    dir:         &str,
+    connection:  &Connection,
+    order:       ApplyOrder,
+    keep_going:  bool,
+    diagnostics: &Diagnostics,
+) -> Result<(), SplitError> {
+    let files = files(dir, order)?;
+    connection.check()?;
+    let mut applied = 0;
+    let mut failed = 0;
+    for file in files.iter() {
+        match connection.run_file(&Path::new(dir).join(file)) {
+            Ok(()) => {
+                println!("ok      {}", file);
+                applied += 1;
+            },
+            Err(e) => {
+                println!("failed  {}", file);
+                diagnostics.error(&format!("{}: {}", file, e), None);
+                failed += 1;
+                if !keep_going {
+                    break;
+                }
+            },
+        }
+    }
+    if !cli.quiet {
+        eprintln!("{} file(s) applied to {}: {} failed, {} not run",
+            applied, connection.describe(), failed, files.len() - applied - failed);
+    }
+
+    if failed > 0 {
+        return Err(SplitError::Server(format!("{} file(s) could not be applied", failed)));
+    }
+    Ok(())
+}
@@ -0,0 +1,171 @@
+/*
+ * comparison of the input against an existing split directory, e.g. to fail
+ * a CI build when the committed files are out of date
+ */
+use crate::cli::Cli;
+use crate::diagnostics::Diagnostics;
+use crate::error::{ IoContext, SplitError };
+use crate::output::{ Scratch, has_contents };
+use crate::splitter::{ Scanner, open_input, split, trim_out_dir };
+use crate::stale::find_stale_files;
+use crate::summary::Summary;
+use indicatif::ProgressBar;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, Copy)]
+pub enum Drift {
+    /// the file exists, but with different content
+    Differs,
+    /// there is no file for the object
+    Missing,
+    /// the file doesn't belong to any object in the input
+    Extra,
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Drift::Differs => "differs",
+            Drift::Missing => "missing",
+            Drift::Extra   => "extra",
+        };
+        f.pad(s)
+    }
+}
+
+/// compare every object in the input described by `cli` with its file in
+/// `cli.out_dir`, printing one line per difference to stdout
+pub fn check(cli: &Cli, diagnostics: &mut Diagnostics) -> Result<(), SplitError> {
+    let out_dir = trim_out_dir(&cli.out_dir);
+    let (mut reader, progress) = open_input(cli)?;
+    diagnostics.attach(&progress);
+    diagnostics.install_panic_hook();
+
+    let mut summary = Summary::new();
+    let mut scanner = Scanner::new(cli, diagnostics, progress.clone(), &out_dir, None)?;
+    let mut drift: Vec<(Drift, String)> = Vec::new();
+    let mut object_paths: HashSet<String> = HashSet::new();
+    scanner.scan(&mut reader, &mut summary, |object, summary| {
+        summary.record_object(object.record.object_type.to_owned());
+        let path = Path::new(&object.record.path);
+        if !path.is_file() {
+            drift.push((Drift::Missing, object.relative_path.to_owned()));
+        } else if !has_contents(path, object.content.as_bytes()) {
+            drift.push((Drift::Differs, object.relative_path.to_owned()));
+        }
+        object_paths.insert(object.relative_path);
+        Ok(())
+    })?;
+    progress.finish_and_clear();
+
+    let type_folders = scanner.type_folders(&out_dir);
+    for path in find_stale_files(&out_dir, &type_folders, &object_paths)? {
+        let relative = path.strip_prefix(&out_dir)
+            .unwrap_or(&path)
+            .display()
+            .to_string();
+        drift.push((Drift::Extra, relative));
+    }
+
+    report(cli, out_dir, &format!("{} object(s)", summary.objects_written), &drift)
+}
+
+/// print `drift` to stdout, one line per difference, and unless `quiet` a
+/// count of each kind to stderr
+pub fn print_drift(quiet: bool, checked: &str, drift: &[(Drift, String)]) {
+    for (kind, path) in drift.iter() {
+        println!("{:<7}  {}", kind, path);
+    }
+    if !quiet {
+        let count = |k: fn(&Drift) -> bool| drift.iter().filter(|(d, _)| k(d)).count();
+        eprintln!("{} checked: {} differ, {} missing, {} extra",
+            checked,
+            count(|d| matches!(d, Drift::Differs)),
+            count(|d| matches!(d, Drift::Missing)),
+            count(|d| matches!(d, Drift::Extra)));
+    }
+}
+
+/// print the differences found in `out_dir`, and fail if there are any
+fn report(cli: &Cli, out_dir: String, checked: &str, drift: &[(Drift, String)]) -> Result<(), SplitError> {
+    print_drift(cli.quiet, checked, drift);
+    if !drift.is_empty() {
+        return Err(SplitError::Drift { dir: out_dir, count: drift.len() });
+    }
+    Ok(())
+}
+
+/// the paths (relative to `root`, with `/` separators) of the files in `dir`
+/// and its subdirectories
+fn files_in(root: &Path, dir: &Path, files: &mut Vec<String>) -> Result<(), SplitError> {
+    let display = dir.display().to_string();
+    for entry in fs::read_dir(dir).create_context(&display)? {
+        let path = entry.create_context(&display)?.path();
+        if path.is_dir() {
+            files_in(root, &path, files)?;
+        } else {
+            let relative: Vec<String> = path.strip_prefix(root)
+                .unwrap_or(&path)
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            files.push(relative.join("/"));
+        }
+    }
+    Ok(())
+}
+
+/// --check: split the input described by `cli` into a temporary directory,
+/// with all its other options, and compare every file written there with
+/// the one in `cli.out_dir`, which is left untouched
+pub fn check_split(cli: &Cli, source: &str, diagnostics: &mut Diagnostics) -> Result<(), SplitError> {
+    let out_dir = trim_out_dir(&cli.out_dir);
+    let scratch = Scratch::new("check");
+    let scratch_dir = scratch.0.display().to_string();
+    let split_cli = Cli {
+        out_dir:      scratch_dir.to_owned(),
+        quiet:        true,
+        verbose:      0,
+        no_atomic:    true,
+        clean:        false,
+        delete_stale: false,
+        incremental:  false,
+        json:         None,
+        check:        false,
+        changed_only: false,
+        ..cli.clone()
+    };
+    split(&split_cli, source, diagnostics)?;
+
+    let mut written = Vec::new();
+    files_in(&scratch.0, &scratch.0, &mut written)?;
+    written.sort();
+    let mut drift: Vec<(Drift, String)> = Vec::new();
+    for relative in written.iter() {
+        let path = Path::new(&out_dir).join(relative);
+        if !path.is_file() {
+            drift.push((Drift::Missing, relative.to_owned()));
+        } else {
+            let contents = fs::read(scratch.0.join(relative)).create_context(relative)?;
+            if !has_contents(&path, &contents) {
+                drift.push((Drift::Differs, relative.to_owned()));
+            }
+        }
+    }
+
+    let scanner = Scanner::new(cli, diagnostics, ProgressBar::hidden(), &out_dir, None)?;
+    let type_folders = scanner.type_folders(&out_dir);
+    let keep: HashSet<String> = written.iter().cloned().collect();
+    for path in find_stale_files(&out_dir, &type_folders, &keep)? {
+        let relative = path.strip_prefix(&out_dir)
+            .unwrap_or(&path)
+            .display()
+            .to_string();
+        drift.push((Drift::Extra, relative));
+    }
+
+    report(cli, out_dir, &format!("{} file(s)", written.len()), &drift)
+}
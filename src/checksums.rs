@@ -0,0 +1,39 @@
+/*
+ * checksum files (in the format understood by `sha256sum -c`) covering every
+ * file written during a run
+ */
+use crate::error::SplitError;
+use crate::output::Output;
+use crate::record::ObjectRecord;
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ChecksumAlgorithm {
+    Sha256,
+}
+
+impl ChecksumAlgorithm {
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Sha256 => "SHA256SUMS",
+        }
+    }
+}
+
+/// write a checksum file into the root of `output`, with one line per object
+/// containing its hash and its path relative to the root
+pub fn write_checksums(
+    output:    &mut Output,
+    records:   &[ObjectRecord],
+    algorithm: ChecksumAlgorithm,
+) -> Result<(), SplitError> {
+    let mut contents = String::new();
+    for record in records {
+        let hash = match algorithm {
+            ChecksumAlgorithm::Sha256 => &record.sha256,
+        };
+        contents.push_str(&format!("{}  {}\n",
+            hash, output.relative_path(&record.path)));
+    }
+    output.write_root_file(algorithm.file_name(), contents.as_bytes())
+}
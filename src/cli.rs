@@ -0,0 +1,360 @@
+/*
+ * command-line interface
+ */
+use crate::apply::ApplyOrder;
+use crate::checksums::ChecksumAlgorithm;
+use crate::dialect::Dialect;
+use crate::exit;
+use crate::graph::GraphFormat;
+use crate::keywords::KeywordCase;
+use crate::migrations::OutputFormat;
+use crate::object::{ Layout, OnDuplicate, PermissionPlacement, Placement, PropertyPlacement };
+use crate::output::OnExisting;
+use crate::record::EventFormat;
+use crate::transform::LineEndings;
+use clap::{ ArgAction, Parser, Subcommand, ValueEnum };
+use regex::Regex;
+
+#[derive(Clone, Parser)]
+#[clap(author, version, about, long_about = None, args_conflicts_with_subcommands = true, after_help = exit::HELP)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[arg(short = 'd', long = "out-dir", required = false, default_value_t = String::from("."), help = "Output directory to create files")]
+    pub out_dir: String,
+    #[arg(short = 'n', long = "only_names", required = false, default_value_t = false, help = "Exclude schema-name from filenames")]
+    pub only_object_names: bool,
+    #[arg(short = 'v', long = "verbose", action = ArgAction::Count, help = "Verbose output (-v lists files created, -vv also lists skipped lines)")]
+    pub verbose: u8,
+    #[arg(short = 'q', long = "quiet", required = false, default_value_t = false, conflicts_with = "verbose", help = "Only print errors")]
+    pub quiet: bool,
+    #[arg(long = "require-objects", required = false, default_value_t = false, help = "Fail if no objects were found in the input")]
+    pub require_objects: bool,
+    #[arg(long = "dialect", required = false, value_enum, default_value_t = Dialect::Ssms, global = true, help = "Tool that generated the input, which decides how objects are recognized")]
+    pub dialect: Dialect,
+    #[arg(long = "split-on", required = false, value_name = "REGEX", value_parser = parse_regex, global = true, conflicts_with = "dialect", help = "Start a new file at every line that matches REGEX, instead of at object headers, to split any text file")]
+    pub split_on: Option<Regex>,
+    #[arg(long = "header-pattern", required = false, value_name = "REGEX", value_parser = parse_header_pattern, global = true, conflicts_with_all = ["dialect", "split_on"], help = "Recognize object headers with REGEX instead of the built-in pattern; its named groups type, name and (optionally) schema are the object's")]
+    pub header_pattern: Option<Regex>,
+    #[arg(long = "name-group", required = false, value_name = "N", default_value_t = 1, requires = "split_on", global = true, help = "Name the files of --split-on after capture group N of the line that starts them")]
+    pub name_group: usize,
+    #[arg(long = "config", required = false, value_name = "FILE", global = true, help = "TOML file with additional settings, e.g. object types to recognize")]
+    pub config: Option<String>,
+    #[arg(long = "strict", required = false, default_value_t = false, global = true, help = "Fail if an object header can't be parsed or names an unknown type, or the input seems to be cut off")]
+    pub strict: bool,
+    #[arg(long = "no-color", required = false, default_value_t = false, help = "Don't use color in error and warning messages")]
+    pub no_color: bool,
+    #[arg(short = 'w', long = "windows-1252", required = false, default_value_t = false, help = "specify that input files are using windows-1252 encoding instead of UTF-8")]
+    pub windows_1252: bool,
+    #[arg(short = 'z', long = "zip", required = false, help = "path to zip file to create and place results")]
+    pub zip: Option<String>,
+    #[arg(long = "data-dir", required = false, value_name = "DIR", conflicts_with = "no_data", help = "Write data scripts to DIR (or a zip file, if it ends in .zip) instead of the output")]
+    pub data_dir: Option<String>,
+    #[arg(long = "clean", required = false, default_value_t = false, help = "Remove existing object type folders from the output directory before writing")]
+    pub clean: bool,
+    #[arg(long = "incremental", required = false, default_value_t = false, conflicts_with_all = ["zip", "clean"], help = "Don't rewrite files whose content hasn't changed")]
+    pub incremental: bool,
+    #[arg(long = "delete-stale", required = false, default_value_t = false, conflicts_with = "zip", help = "After splitting, delete .sql files in the object type folders that don't belong to an object in the input")]
+    pub delete_stale: bool,
+    #[arg(long = "no-atomic", required = false, default_value_t = false, help = "Write files directly into place instead of staging them in a temporary directory")]
+    pub no_atomic: bool,
+    #[arg(long = "json", required = false, num_args = 0..=1, require_equals = true, default_missing_value = "-", value_name = "FILE", help = "Write a JSON summary of the run to FILE (or stdout if no FILE is given)")]
+    pub json: Option<String>,
+    #[arg(long = "events", required = false, value_enum, help = "Print an event for every object to stdout as it is split")]
+    pub events: Option<EventFormat>,
+    #[arg(long = "manifest", required = false, default_value_t = false, help = "Write a manifest.json describing every object to the output root")]
+    pub manifest: bool,
+    #[arg(long = "sorted", required = false, default_value_t = false, global = true, help = "List objects by path rather than in input order in manifest.json, checksum files and the inventory")]
+    pub sorted: bool,
+    #[arg(long = "preset", required = false, value_enum, global = true, help = "Turn on a set of options at once: git for --strip-script-date, --strip-set-options, --line-endings lf and --sorted")]
+    pub preset: Option<Preset>,
+    #[arg(long = "changed-only", required = false, default_value_t = false, conflicts_with_all = ["zip", "clean"], help = "Only write the objects whose files differ from the last commit of the git repository the output directory is in")]
+    pub changed_only: bool,
+    #[arg(long = "check", required = false, default_value_t = false, conflicts_with_all = ["zip", "data_dir", "git_commit", "exec", "print0", "inventory"], help = "Split into a temporary directory and report the files that would change in the output directory, without touching it; fails if there are any")]
+    pub check: bool,
+    #[arg(long = "build-order", required = false, default_value_t = false, help = "Write build-order.txt, listing the files in an order they can be deployed in based on the objects they reference")]
+    pub build_order: bool,
+    #[arg(long = "emit-index", required = false, num_args = 0..=1, require_equals = true, default_missing_value = "index.sql", value_name = "NAME", help = "Write a script to the output root (index.sql unless NAME is given) that includes every file with sqlcmd's :r, by type and, with --build-order, by dependency")]
+    pub emit_index: Option<String>,
+    #[arg(long = "git-commit", required = false, value_name = "MESSAGE", conflicts_with = "zip", help = "Commit the files that were written or deleted to the git repository the output directory is in")]
+    pub git_commit: Option<String>,
+    #[arg(long = "checksums", required = false, value_enum, help = "Write a checksum file (e.g. SHA256SUMS) covering every file created")]
+    pub checksums: Option<ChecksumAlgorithm>,
+    #[arg(long = "inventory", required = false, value_name = "CSV_FILE", help = "Write a CSV listing of every object created")]
+    pub inventory: Option<String>,
+    #[arg(long = "print0", required = false, default_value_t = false, conflicts_with_all = ["zip", "verbose", "events"], help = "Print the path of every file created to stdout, separated by NUL characters (for xargs -0)")]
+    pub print0: bool,
+    #[arg(long = "exec", required = false, value_name = "COMMAND", conflicts_with = "zip", help = "Run COMMAND for every file written, with {} replaced by the file's path")]
+    pub exec: Option<String>,
+    #[arg(long = "exec-jobs", required = false, value_name = "N", requires = "exec", help = "Run at most N --exec commands at the same time [default: number of CPUs]")]
+    pub exec_jobs: Option<usize>,
+    #[arg(long = "header-file", required = false, value_name = "TEMPLATE", global = true, help = "Prepend the contents of TEMPLATE to every file; {schema}, {name}, {type} and {database} are replaced with the object's")]
+    pub header_file: Option<String>,
+    #[arg(long = "footer-file", required = false, value_name = "TEMPLATE", global = true, help = "Append the contents of TEMPLATE to every file, with the same placeholders as --header-file")]
+    pub footer_file: Option<String>,
+    #[arg(long = "footer-text", required = false, value_name = "TEXT", global = true, conflicts_with = "footer_file", help = "Append TEXT (as a line) to every file, with the same placeholders as --header-file")]
+    pub footer_text: Option<String>,
+    #[arg(long = "on-existing", required = false, value_enum, default_value_t = OnExisting::Overwrite, conflicts_with_all = ["zip", "clean"], help = "What to do with files that already exist in the output directory")]
+    pub on_existing: OnExisting,
+    #[arg(long = "skip-empty", required = false, default_value_t = false, global = true, help = "Don't write objects that have nothing but their header (and SET options)")]
+    pub skip_empty: bool,
+    #[arg(long = "on-duplicate", required = false, value_enum, default_value_t = OnDuplicate::KeepLast, global = true, help = "What to do with an object that appears more than once in the input")]
+    pub on_duplicate: OnDuplicate,
+    #[arg(long = "no-clobber", required = false, default_value_t = false, conflicts_with_all = ["zip", "clean", "on_existing"], help = "Don't overwrite existing files (same as --on-existing skip)")]
+    pub no_clobber: bool,
+    #[arg(long = "statistics", required = false, value_enum, default_value_t = Placement::Folder, global = true, help = "Where to write scripted statistics: in a Statistic folder, or in their table's file")]
+    pub statistics: Placement,
+    #[arg(long = "triggers", required = false, value_enum, default_value_t = Placement::Folder, global = true, help = "Where to write DML triggers: in a Trigger folder, or in their table's file")]
+    pub triggers: Placement,
+    #[arg(long = "indexes", required = false, value_enum, default_value_t = Placement::Folder, global = true, help = "Where to write indexes: in an Index folder, or in their table's file")]
+    pub indexes: Placement,
+    #[arg(long = "no-data", required = false, default_value_t = false, global = true, help = "Leave out the INSERT statements of scripts that include data, instead of writing them to the Data folder")]
+    pub no_data: bool,
+    #[arg(long = "data-chunk-rows", required = false, value_name = "N", global = true, help = "Split data scripts with more than N INSERT statements into numbered part files")]
+    pub data_chunk_rows: Option<usize>,
+    #[arg(long = "max-file-size", required = false, value_name = "SIZE", value_parser = parse_size, global = true, help = "Split files larger than SIZE (e.g. 50MB) into numbered part files at GO separators")]
+    pub max_file_size: Option<usize>,
+    #[arg(long = "redact", required = false, value_name = "FILE", global = true, help = "Mask the values of the columns listed in FILE (TOML) in INSERT statements")]
+    pub redact: Option<String>,
+    #[arg(long = "mask-secrets", required = false, default_value_t = false, global = true, help = "Replace passwords, password hashes and secrets (e.g. of logins and credentials) with placeholders")]
+    pub mask_secrets: bool,
+    #[arg(long = "database-folders", required = false, default_value_t = false, global = true, help = "Write every object into a folder named after its database")]
+    pub database_folders: bool,
+    #[arg(long = "layout", required = false, value_enum, default_value_t = Layout::Types, global = true, help = "Folders to write objects to: one per object type, or those of Redgate SQL Source Control (which also keeps triggers, indexes and statistics with their table)")]
+    pub layout: Layout,
+    #[arg(long = "output-format", required = false, value_enum, default_value_t = OutputFormat::Folders, global = true, help = "How files are named and laid out: a folder per object type, or for a migration tool")]
+    pub output_format: OutputFormat,
+    #[arg(long = "start-version", required = false, default_value_t = 1, value_name = "N", global = true, help = "Number of the first file with --output-format flyway or dbup")]
+    pub start_version: u64,
+    #[arg(long = "changelog", required = false, default_value_t = String::from("changelog.xml"), value_name = "NAME", global = true, help = "Name of the changelog written with --output-format liquibase; it's YAML if NAME ends in .yaml or .yml")]
+    pub changelog: String,
+    #[arg(long = "table-parts", required = false, default_value_t = false, global = true, conflicts_with = "indexes", help = "Write each table to <name>.table.sql, its constraints to <name>.constraints.sql and its indexes to <name>.indexes.sql")]
+    pub table_parts: bool,
+    #[arg(long = "extended-properties", required = false, value_enum, default_value_t = PropertyPlacement::WithObject, global = true, help = "Where to write extended properties: with the object they follow if it's theirs, or always in an ExtendedProperties folder")]
+    pub extended_properties: PropertyPlacement,
+    #[arg(long = "permissions", required = false, value_enum, default_value_t = PermissionPlacement::WithObject, global = true, help = "Where to write GRANT, DENY and REVOKE statements: with the object they follow, or separately in Security/Permissions")]
+    pub permissions: PermissionPlacement,
+    #[arg(long = "strip-script-date", required = false, default_value_t = false, global = true, help = "Remove the volatile \"Script Date: ...\" from object headers")]
+    pub strip_script_date: bool,
+    #[arg(long = "strip-set-options", required = false, default_value_t = false, global = true, help = "Remove the SET ANSI_NULLS ON / SET QUOTED_IDENTIFIER ON batches before each object")]
+    pub strip_set_options: bool,
+    #[arg(long = "strip-comments", required = false, default_value_t = false, global = true, help = "Remove comments (except the object header) from every object's script")]
+    pub strip_comments: bool,
+    #[arg(long = "omit-assembly-binaries", required = false, default_value_t = false, global = true, help = "Replace the binary in CLR assembly scripts with its size and SHA-256")]
+    pub omit_assembly_binaries: bool,
+    #[arg(long = "no-use", required = false, default_value_t = false, global = true, help = "Don't put the USE statement for the object's database at the top of every file")]
+    pub no_use: bool,
+    #[arg(long = "rename-db", required = false, value_name = "OLD=NEW", value_parser = parse_pair, action = ArgAction::Append, global = true, help = "Replace database OLD with NEW in USE statements, e.g. PROD=$(DatabaseName) (can be repeated)")]
+    pub rename_db: Vec<(String, String)>,
+    #[arg(long = "create-or-alter", required = false, default_value_t = false, global = true, help = "Turn CREATE PROCEDURE/FUNCTION/VIEW/TRIGGER into CREATE OR ALTER (SQL Server 2016 SP1 and later)")]
+    pub create_or_alter: bool,
+    #[arg(long = "drop-first", required = false, default_value_t = false, global = true, help = "Put a DROP <type> IF EXISTS batch before every object's definition")]
+    pub drop_first: bool,
+    #[arg(long = "idempotent", required = false, default_value_t = false, global = true, conflicts_with_all = ["create_or_alter", "drop_first"], help = "Wrap every object in existence checks so its script can be run more than once")]
+    pub idempotent: bool,
+    #[arg(long = "format", required = false, default_value_t = false, global = true, help = "Run every object's script through a SQL formatter for a consistent style")]
+    pub format: bool,
+    #[arg(long = "line-endings", required = false, value_enum, default_value_t = LineEndings::Preserve, global = true, help = "Line endings of the files written")]
+    pub line_endings: LineEndings,
+    #[arg(long = "keywords", required = false, value_enum, default_value_t = KeywordCase::Preserve, global = true, help = "Put T-SQL keywords (outside of strings and comments) in upper or lower case")]
+    pub keywords: KeywordCase,
+    #[arg(long = "sqlcmd", required = false, default_value_t = false, global = true, help = "Process sqlcmd :setvar lines and substitute $(name) variable references")]
+    pub sqlcmd: bool,
+    #[arg(long = "var", required = false, value_name = "NAME=VALUE", value_parser = parse_pair, action = ArgAction::Append, global = true, help = "Set sqlcmd variable NAME, overriding :setvar (can be repeated; implies --sqlcmd)")]
+    pub var: Vec<(String, String)>,
+    #[arg(long = "expand-includes", required = false, default_value_t = false, global = true, help = "Replace sqlcmd :r <file> lines with the contents of the file (relative to the including file)")]
+    pub expand_includes: bool,
+    #[arg(long = "validate", required = false, default_value_t = false, global = true, help = "Warn about objects whose script is incomplete, e.g. truncated or corrupted definitions")]
+    pub validate: bool,
+    // remaining arguments are file-paths
+    #[arg(required = false, help = "File(s) to process")]
+    pub in_file: Option<String>,
+}
+
+/// parse a `KEY=VALUE` pair
+fn parse_pair(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() =>
+            Ok((key.to_string(), value.to_string())),
+        _ => Err(String::from("expected <KEY>=<VALUE>")),
+    }
+}
+
+/// parse a size in bytes, with an optional KB, MB or GB suffix (powers of
+/// 1024)
+fn parse_size(s: &str) -> Result<usize, String> {
+    let upper = s.trim().to_ascii_uppercase();
+    let (number, unit) = match upper.find(|c: char| !c.is_ascii_digit()) {
+        Some(at) => upper.split_at(at),
+        None     => (upper.as_str(), ""),
+    };
+    let unit = match unit.trim() {
+        "" | "B"   => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        _          => return Err(format!("unknown unit {:?} (expected KB, MB or GB)", unit.trim())),
+    };
+    match number.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n * unit),
+        _              => Err(String::from("expected a size like 50MB")),
+    }
+}
+
+/// parse a regular expression
+fn parse_regex(s: &str) -> Result<Regex, String> {
+    Regex::new(s).map_err(|e| e.to_string())
+}
+
+/// parse --header-pattern, which needs the groups that make up an object
+fn parse_header_pattern(s: &str) -> Result<Regex, String> {
+    let pattern = parse_regex(s)?;
+    for group in ["type", "name"] {
+        if !pattern.capture_names().any(|name| name == Some(group)) {
+            return Err(format!("the pattern has no (?P<{}>...) group", group));
+        }
+    }
+    Ok(pattern)
+}
+
+impl Cli {
+    /// turn on the options of --preset, if it was given
+    pub fn apply_preset(&mut self) {
+        match self.preset {
+            Some(Preset::Git) => {
+                self.strip_script_date = true;
+                self.strip_set_options = true;
+                if self.line_endings == LineEndings::Preserve {
+                    self.line_endings = LineEndings::Lf;
+                }
+                self.sorted = true;
+            },
+            None => (),
+        }
+    }
+
+    /// --on-existing, taking --no-clobber into account
+    pub fn on_existing(&self) -> OnExisting {
+        if self.no_clobber {
+            OnExisting::Skip
+        } else {
+            self.on_existing
+        }
+    }
+
+    /// Redgate's layout keeps what belongs to a table in the table's file
+    fn placement(&self, placement: Placement) -> Placement {
+        if self.layout == Layout::Redgate {
+            Placement::WithTable
+        } else {
+            placement
+        }
+    }
+
+    pub fn statistics(&self) -> Placement {
+        self.placement(self.statistics)
+    }
+
+    pub fn triggers(&self) -> Placement {
+        self.placement(self.triggers)
+    }
+
+    pub fn indexes(&self) -> Placement {
+        self.placement(self.indexes)
+    }
+}
+
+/// sets of options for common uses
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Preset {
+    // output that changes as little as possible between runs, for clean git
+    // history
+    Git,
+}
+
+#[derive(Clone, Subcommand)]
+pub enum Command {
+    #[command(about = "Print a man page (roff) generated from the CLI definition")]
+    Mangen,
+    #[command(about = "Report objects that differ from, are missing from or are extra in an existing split directory")]
+    Check {
+        #[arg(help = "Dump to compare")]
+        in_file: String,
+        #[arg(help = "Split directory to compare against")]
+        out_dir: String,
+        #[arg(short = 'n', long = "only_names", required = false, default_value_t = false, help = "Exclude schema-name from filenames")]
+        only_object_names: bool,
+        #[arg(short = 'w', long = "windows-1252", required = false, default_value_t = false, help = "specify that input files are using windows-1252 encoding instead of UTF-8")]
+        windows_1252: bool,
+        #[arg(short = 'q', long = "quiet", required = false, default_value_t = false, help = "Only print differences and errors")]
+        quiet: bool,
+    },
+    #[command(about = "Script the procedures, views, functions and triggers of a database on a server, and split them")]
+    Script {
+        #[arg(short = 'c', long = "connection", value_name = "CONNECTION_STRING", help = "Connection string, e.g. \"Server=db1;Database=Sales;Integrated Security=true\"")]
+        connection: String,
+        #[arg(short = 'd', long = "out-dir", required = false, default_value_t = String::from("."), help = "Output directory to create files")]
+        out_dir: String,
+        #[arg(short = 'n', long = "only_names", required = false, default_value_t = false, help = "Exclude schema-name from filenames")]
+        only_object_names: bool,
+        #[arg(short = 'q', long = "quiet", required = false, default_value_t = false, help = "Only print errors")]
+        quiet: bool,
+    },
+    #[command(about = "Run the files of a split directory against a database on a server")]
+    Apply {
+        #[arg(help = "Split directory to apply")]
+        dir: String,
+        #[arg(short = 'c', long = "connection", value_name = "CONNECTION_STRING", help = "Connection string, e.g. \"Server=db1;Database=Sales;Integrated Security=true\"")]
+        connection: String,
+        #[arg(long = "order", required = false, value_enum, default_value_t = ApplyOrder::Type, help = "Order to run the files in")]
+        order: ApplyOrder,
+        #[arg(short = 'k', long = "keep-going", required = false, default_value_t = false, help = "Carry on with the next file after a failure")]
+        keep_going: bool,
+        #[arg(short = 'q', long = "quiet", required = false, default_value_t = false, help = "Only print the files run and errors")]
+        quiet: bool,
+    },
+    #[command(name = "diff-live", about = "Report procedures, views, functions and triggers that differ from, are missing from or are extra in a database on a server")]
+    DiffLive {
+        #[arg(help = "Dump to compare")]
+        in_file: String,
+        #[arg(short = 'c', long = "connection", value_name = "CONNECTION_STRING", help = "Connection string, e.g. \"Server=db1;Database=Sales;Integrated Security=true\"")]
+        connection: String,
+        #[arg(short = 'w', long = "windows-1252", required = false, default_value_t = false, help = "specify that input files are using windows-1252 encoding instead of UTF-8")]
+        windows_1252: bool,
+        #[arg(short = 'q', long = "quiet", required = false, default_value_t = false, help = "Only print differences and errors")]
+        quiet: bool,
+    },
+    #[command(about = "Print a graph of which objects reference which, e.g. for Graphviz")]
+    Graph {
+        #[arg(help = "Dump to read")]
+        in_file: String,
+        // --format is taken by the (global) formatting switch
+        #[arg(short = 'f', long = "graph-format", required = false, value_enum, default_value_t = GraphFormat::Dot, help = "Format of the graph")]
+        graph_format: GraphFormat,
+        #[arg(short = 'n', long = "only_names", required = false, default_value_t = false, help = "Exclude schema-name from filenames")]
+        only_object_names: bool,
+        #[arg(short = 'w', long = "windows-1252", required = false, default_value_t = false, help = "specify that input files are using windows-1252 encoding instead of UTF-8")]
+        windows_1252: bool,
+        #[arg(short = 'q', long = "quiet", required = false, default_value_t = false, help = "Only print the graph and errors")]
+        quiet: bool,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from([&["sql-splitter"], args].concat()).unwrap()
+    }
+
+    #[test]
+    fn json_only_takes_a_file_after_an_equals_sign() {
+        let cli = parse(&["--json", "dump.sql"]);
+        assert_eq!(cli.json.as_deref(), Some("-"));
+        assert_eq!(cli.in_file.as_deref(), Some("dump.sql"));
+        let cli = parse(&["--json=summary.json", "dump.sql"]);
+        assert_eq!(cli.json.as_deref(), Some("summary.json"));
+        assert_eq!(cli.in_file.as_deref(), Some("dump.sql"));
+    }
+}
@@ -0,0 +1,49 @@
+/*
+ * the configuration file (--config), for settings that don't fit on the
+ * command line
+ */
+use crate::cli::Cli;
+use crate::error::SplitError;
+use serde::Deserialize;
+use std::fs;
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// object types in addition to the built-in ones, as [[object_type]]
+    /// tables
+    #[serde(default, rename = "object_type")]
+    pub object_types: Vec<CustomType>,
+}
+
+/// an object type declared in the configuration file
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomType {
+    /// the type as it appears in object headers (`Object: <header> [...]`)
+    pub header:           String,
+    /// folder (relative to the output root) that objects of this type are
+    /// written to; the header keyword if not given
+    pub folder:           Option<String>,
+    /// whether headers name objects as [schema].[name] rather than [name]
+    #[serde(default = "schema_qualified")]
+    pub schema_qualified: bool,
+}
+
+fn schema_qualified() -> bool {
+    true
+}
+
+impl Config {
+    /// the configuration file given with --config, or the defaults
+    pub fn load(cli: &Cli) -> Result<Self, SplitError> {
+        let path = match cli.config.as_ref() {
+            Some(path) => path,
+            None       => return Ok(Config::default()),
+        };
+        let text = fs::read_to_string(path)
+            .map_err(|source| SplitError::OpenInput { path: path.to_owned(), source })?;
+        toml::from_str(&text)
+            .map_err(|e| SplitError::Config { path: path.to_owned(), message: e.to_string() })
+    }
+}
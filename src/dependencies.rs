@@ -0,0 +1,285 @@
+/*
+ * references between the objects that were written (views on tables, procs
+ * calling procs, foreign keys, ...), to deploy them in an order that works
+ * on an empty database (--build-order)
+ */
+use crate::error::SplitError;
+use crate::object::{ ObjectType, deploy_rank };
+use crate::output::Output;
+use crate::record::ObjectRecord;
+use crate::tokenizer::{ Span, Tokenizer };
+use regex::{ Captures, Regex };
+use std::collections::{ BTreeSet, HashMap };
+
+pub const BUILD_ORDER_FILE: &str = "build-order.txt";
+
+/// (database, schema, name), lowercased
+type Key = (String, String, String);
+
+/// a file that was written, and what it references
+struct Node {
+    path:        String,
+    object_type: String,
+    key:         Key,
+    references:  Vec<Key>,
+}
+
+/// the files in an order they can be deployed in
+pub struct BuildOrder<'a> {
+    /// every file, with the ones in `cyclic` at the end
+    pub paths:  Vec<&'a str>,
+    /// files that depend on themselves (through others), or on such files,
+    /// so they can't be put in order
+    pub cyclic: Vec<&'a str>,
+}
+
+pub struct Dependencies {
+    /// matches (possibly qualified) names, along with the keyword before
+    /// them that makes a bare name an object's name
+    names:   Regex,
+    /// a single part of a qualified name
+    part:    Regex,
+    /// matches string literals, and the quoted identifiers that might
+    /// contain quotes
+    strings: Regex,
+    nodes:   Vec<Node>,
+    /// where each file's node is in `nodes`, by its path
+    indexes: HashMap<String, usize>,
+}
+
+/// the parts of the file that aren't comments
+fn without_comments(content: &str) -> String {
+    let mut tokenizer = Tokenizer::new();
+    let mut code = String::with_capacity(content.len());
+    for line in content.split_inclusive('\n') {
+        let mut copied = 0;
+        for span in tokenizer.spans(line) {
+            if let Span::Comment(range) = span {
+                code.push_str(&line[copied..range.start]);
+                copied = range.end;
+            }
+        }
+        code.push_str(&line[copied..]);
+    }
+    code
+}
+
+/// whether files of `object_type` define the object they're named after,
+/// rather than adding to an object defined elsewhere
+fn defines(object_type: &str) -> bool {
+    ![
+        ObjectType::Data.to_string().as_str(),
+        ObjectType::ExtendedProperty.to_string().as_str(),
+        ObjectType::Permission.to_string().as_str(),
+        "Constraints",
+        "Indexes",
+    ].contains(&object_type)
+}
+
+fn key(database: &str, schema: &str, name: &str) -> Key {
+    (database.to_lowercase(), schema.to_lowercase(), name.to_lowercase())
+}
+
+impl Dependencies {
+    pub fn new() -> Self {
+        let name = r"(?:\[(?:[^\]]|\]\])+\]|\w+)";
+        Dependencies {
+            names:   Regex::new(&format!(r"(?i)(?:\b(FROM|JOIN|INTO|UPDATE|EXEC|EXECUTE|REFERENCES)\s+)?({name}(?:\s*\.\s*{name}){{0,3}})"))
+                .expect("error compiling object name regular expression"),
+            part:    Regex::new(r"\[((?:[^\]]|\]\])+)\]|(\w+)")
+                .expect("error compiling name part regular expression"),
+            strings: Regex::new(r#"\[(?:[^\]]|\]\])*\]|"[^"]*"|'(?:[^']|'')*'"#)
+                .expect("error compiling string literal regular expression"),
+            nodes:   Vec::new(),
+            indexes: HashMap::new(),
+        }
+    }
+
+    /// add the file of `record` (at `relative_path` in the output), whose
+    /// content is `content`; a file that was added before is replaced
+    pub fn add(&mut self, record: &ObjectRecord, relative_path: &str, content: &str) {
+        let code = without_comments(content);
+        let code = self.strings.replace_all(&code, |caps: &Captures| {
+            if caps[0].starts_with('\'') {
+                String::from("''")
+            } else {
+                caps[0].to_string()
+            }
+        });
+
+        let mut references = Vec::new();
+        for caps in self.names.captures_iter(&code) {
+            let parts: Vec<String> = self.part.captures_iter(&caps[2])
+                .map(|part| match part.get(1) {
+                    Some(quoted) => quoted.as_str().replace("]]", "]"),
+                    None         => part[2].to_string(),
+                })
+                .collect();
+            match parts.as_slice() {
+                // a bare name is only an object's name after e.g. FROM, and
+                // is looked up in the object's own schema and then in dbo
+                [name] if caps.get(1).is_some() => {
+                    if !record.schema.is_empty() {
+                        references.push(key(&record.database, &record.schema, name));
+                    }
+                    references.push(key(&record.database, "dbo", name));
+                },
+                [schema, name] => references.push(key(&record.database, schema, name)),
+                [.., database, schema, name] => references.push(key(database, schema, name)),
+                _ => (),
+            }
+        }
+        // objects depend on their schema
+        if !record.schema.is_empty() {
+            references.push(key(&record.database, "", &record.schema));
+        }
+
+        let node = Node {
+            path:        relative_path.to_string(),
+            object_type: record.object_type.to_owned(),
+            key:         key(&record.database, &record.schema, &record.name),
+            references,
+        };
+        match self.indexes.get(&node.path) {
+            Some(&index) => self.nodes[index] = node,
+            None         => {
+                self.indexes.insert(node.path.to_owned(), self.nodes.len());
+                self.nodes.push(node);
+            },
+        }
+    }
+
+    /// for every file, the files it depends on
+    fn edges(&self) -> Vec<BTreeSet<usize>> {
+        // the files that define each object
+        let mut definitions: HashMap<&Key, Vec<usize>> = HashMap::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            if defines(&node.object_type) {
+                definitions.entry(&node.key).or_default().push(index);
+            }
+        }
+        self.nodes.iter()
+            .enumerate()
+            .map(|(index, node)| node.references.iter()
+                .filter_map(|reference| definitions.get(reference))
+                .flatten()
+                .copied()
+                .filter(|&dependency| dependency != index)
+                .collect())
+            .collect()
+    }
+
+    /// every file, along with the files it depends on
+    pub fn graph(&self) -> Vec<(&str, Vec<&str>)> {
+        self.edges().into_iter()
+            .zip(self.nodes.iter())
+            .map(|(dependencies, node)| (
+                node.path.as_str(),
+                dependencies.into_iter().map(|dependency| self.nodes[dependency].path.as_str()).collect(),
+            ))
+            .collect()
+    }
+
+    /// the files, each after the ones it depends on; otherwise they're in
+    /// deployment order by type, and then in the order they were added
+    pub fn build_order(&self) -> BuildOrder<'_> {
+        let edges = self.edges();
+        let mut pending: Vec<usize> = edges.iter().map(BTreeSet::len).collect();
+        let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); self.nodes.len()];
+        for (index, dependencies) in edges.iter().enumerate() {
+            for &dependency in dependencies {
+                dependents[dependency].push(index);
+            }
+        }
+
+        // the files that can go next, by (type rank, index)
+        let rank = |index: usize| (deploy_rank(&self.nodes[index].object_type), index);
+        let mut ready: BTreeSet<(usize, usize)> = (0..self.nodes.len())
+            .filter(|&index| pending[index] == 0)
+            .map(rank)
+            .collect();
+        let mut paths = Vec::with_capacity(self.nodes.len());
+        while let Some((_, index)) = ready.pop_first() {
+            paths.push(self.nodes[index].path.as_str());
+            for &dependent in dependents[index].iter() {
+                pending[dependent] -= 1;
+                if pending[dependent] == 0 {
+                    ready.insert(rank(dependent));
+                }
+            }
+        }
+        let cyclic: Vec<&str> = (0..self.nodes.len())
+            .filter(|&index| pending[index] > 0)
+            .map(|index| self.nodes[index].path.as_str())
+            .collect();
+        paths.extend(cyclic.iter());
+        BuildOrder { paths, cyclic }
+    }
+}
+
+/// write build-order.txt into the root of `output`, with one path (relative
+/// to the root) per line
+pub fn write_build_order(output: &mut Output, order: &BuildOrder) -> Result<(), SplitError> {
+    let mut contents = String::new();
+    for path in order.paths.iter() {
+        contents.push_str(path);
+        contents.push('\n');
+    }
+    output.write_root_file(BUILD_ORDER_FILE, contents.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the dependencies of `objects`, given as (type, name, content) in the
+    /// order they're added
+    fn dependencies(objects: &[(&str, &str, &str)]) -> Dependencies {
+        let mut dependencies = Dependencies::new();
+        for (object_type, name, content) in objects {
+            let path = format!("{}/dbo.{}.sql", object_type, name);
+            let record = ObjectRecord::new(object_type.to_string(), String::from("dbo"), name.to_string(),
+                String::from("Sales"), path.to_owned(), 0);
+            dependencies.add(&record, &path, content);
+        }
+        dependencies
+    }
+
+    #[test]
+    fn objects_come_after_what_they_use_even_if_it_is_defined_later() {
+        // by type alone, the function would go before both views
+        let dependencies = dependencies(&[
+            ("View", "v_Orders", "CREATE VIEW [dbo].[v_Orders] AS SELECT [dbo].[fn_Total](Id) AS Total FROM Orders\n"),
+            ("UserDefinedFunction", "fn_Total", "CREATE FUNCTION [dbo].[fn_Total](@id int) RETURNS money AS\n\
+                BEGIN RETURN (SELECT SUM(Rate) FROM dbo.v_Rates WHERE Id = @id) END\n"),
+            ("View", "v_Rates", "CREATE VIEW [dbo].[v_Rates] AS SELECT Id, Rate FROM [dbo].[Rates]\n"),
+            ("Table", "Rates", "CREATE TABLE [dbo].[Rates] (Id int, Rate money)\n"),
+        ]);
+        let order = dependencies.build_order();
+        assert_eq!(order.paths, [
+            "Table/dbo.Rates.sql",
+            "View/dbo.v_Rates.sql",
+            "UserDefinedFunction/dbo.fn_Total.sql",
+            "View/dbo.v_Orders.sql",
+        ]);
+        assert!(order.cyclic.is_empty());
+    }
+
+    #[test]
+    fn cycles_and_what_depends_on_them_come_last() {
+        let dependencies = dependencies(&[
+            ("StoredProcedure", "p_Report", "CREATE PROCEDURE [dbo].[p_Report] AS SELECT * FROM [dbo].[v_A]\n"),
+            ("View", "v_A", "CREATE VIEW [dbo].[v_A] AS SELECT * FROM [dbo].[v_B]\n"),
+            ("View", "v_B", "CREATE VIEW [dbo].[v_B] AS SELECT * FROM dbo.v_A -- FROM dbo.Orders\n"),
+            ("Table", "Orders", "CREATE TABLE [dbo].[Orders] (Id int)\n"),
+        ]);
+        let order = dependencies.build_order();
+        assert_eq!(order.cyclic, ["StoredProcedure/dbo.p_Report.sql", "View/dbo.v_A.sql", "View/dbo.v_B.sql"]);
+        assert_eq!(order.paths, [
+            "Table/dbo.Orders.sql",
+            "StoredProcedure/dbo.p_Report.sql",
+            "View/dbo.v_A.sql",
+            "View/dbo.v_B.sql",
+        ]);
+    }
+}
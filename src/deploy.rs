@@ -0,0 +1,18 @@
+/*
+ * --emit-index: a script that includes every file with sqlcmd's :r, so the
+ * output can be deployed with a single `sqlcmd -i index.sql`
+ */
+use crate::error::SplitError;
+use crate::output::Output;
+
+/// write the index script `name` into the root of `output`, including the
+/// files at `paths` (relative to the root) in that order
+pub fn write_index(output: &mut Output, name: &str, paths: &[String]) -> Result<(), SplitError> {
+    let mut contents = String::from("-- run from this directory, e.g. sqlcmd -S <server> -d <database> -i ");
+    contents.push_str(name);
+    contents.push_str("\n:on error exit\n");
+    for path in paths {
+        contents.push_str(&format!(":r \"{}\"\n", path));
+    }
+    output.write_root_file(name, contents.as_bytes())
+}
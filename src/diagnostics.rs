@@ -0,0 +1,124 @@
+/*
+ * human-readable (and optionally colored) error and warning messages that
+ * point at the offending line of the input
+ */
+use crate::exit;
+use indicatif::ProgressBar;
+use std::io::{ IsTerminal, Write };
+
+const RED:    &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE:   &str = "\x1b[1;34m";
+const BOLD:   &str = "\x1b[1m";
+const RESET:  &str = "\x1b[0m";
+
+#[derive(Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Clone)]
+pub struct Diagnostics {
+    /// name of the input being read (a path, or "<stdin>")
+    source:   String,
+    color:    bool,
+    progress: ProgressBar,
+}
+
+impl Diagnostics {
+    /// color is used when allowed and stderr is a terminal, unless the
+    /// NO_COLOR environment variable is set
+    pub fn new(source: &str, allow_color: bool) -> Self {
+        let color = allow_color
+            && std::env::var_os("NO_COLOR").is_none()
+            && std::io::stderr().is_terminal();
+        Diagnostics {
+            source:   source.to_string(),
+            color,
+            progress: ProgressBar::hidden(),
+        }
+    }
+
+    /// messages are printed around this progress bar so they don't get
+    /// mangled by it
+    pub fn attach(&mut self, progress: &ProgressBar) {
+        self.progress = progress.clone();
+    }
+
+    fn paint(&self, style: &str, s: &str) -> String {
+        if self.color {
+            format!("{}{}{}", style, s, RESET)
+        } else {
+            s.to_string()
+        }
+    }
+
+    /// format a message, optionally pointing at `line` (line number and text)
+    /// of the input
+    pub fn render(
+        &self,
+        severity: Severity,
+        message:  &str,
+        line:     Option<(usize, &str)>,
+    ) -> String {
+        let label = match severity {
+            Severity::Error   => self.paint(RED, "error"),
+            Severity::Warning => self.paint(YELLOW, "warning"),
+        };
+        let mut out = format!("{}: {}", label, self.paint(BOLD, message));
+        if let Some((line_number, text)) = line {
+            let number = line_number.to_string();
+            let gutter = " ".repeat(number.len());
+            out.push_str(&format!("\n{}{} {}:{}",
+                gutter, self.paint(BLUE, "-->"), self.source, line_number));
+            out.push_str(&format!("\n{} {}",
+                gutter, self.paint(BLUE, "|")));
+            out.push_str(&format!("\n{} {}",
+                self.paint(BLUE, &format!("{} |", number)), text.trim_end()));
+            out.push_str(&format!("\n{} {}",
+                gutter, self.paint(BLUE, "|")));
+        }
+        out
+    }
+
+    pub fn emit(&self, severity: Severity, message: &str, line: Option<(usize, &str)>) {
+        let rendered = self.render(severity, message, line);
+        // not eprintln, which panics if stderr is gone (and panics are
+        // reported through here as well)
+        self.progress.suspend(|| {
+            let _ = writeln!(std::io::stderr(), "{}", rendered);
+        });
+    }
+
+    pub fn error(&self, message: &str, line: Option<(usize, &str)>) {
+        self.emit(Severity::Error, message, line);
+    }
+
+    pub fn warning(&self, message: &str, line: Option<(usize, &str)>) {
+        self.emit(Severity::Warning, message, line);
+    }
+
+    /// report an error and exit with the given status (see `exit`)
+    pub fn fatal(&self, code: i32, message: &str, line: Option<(usize, &str)>) -> ! {
+        self.error(message, line);
+        std::process::exit(code);
+    }
+
+    /// report any remaining panics (i.e. failed `expect`s) as errors rather
+    /// than with the default panic message
+    pub fn install_panic_hook(&self) {
+        let diagnostics = self.clone();
+        std::panic::set_hook(Box::new(move |info| {
+            let payload = info.payload();
+            let message = if let Some(s) = payload.downcast_ref::<&str>() {
+                s.to_string()
+            } else if let Some(s) = payload.downcast_ref::<String>() {
+                s.clone()
+            } else {
+                String::from("unexpected failure")
+            };
+            diagnostics.fatal(exit::INTERNAL, &message, None);
+        }));
+    }
+}
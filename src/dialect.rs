@@ -0,0 +1,87 @@
+/*
+ * the tools whose scripts can be split (--dialect): how each marks where an
+ * object starts, and whether it separates batches with GO; the versions of
+ * SSMS whose headers differ are dialects of their own
+ */
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Dialect {
+    // SQL Server Management Studio's (or SMO's) Generate Scripts
+    Ssms,
+    // SQL Server 2000's Enterprise Manager and Query Analyzer, whose headers
+    // spell types with spaces (Stored Procedure) and leave names unbracketed
+    Ssms2000,
+    // SSMS scripting Azure SQL Database or Synapse, with their types (e.g.
+    // DatabaseScopedCredential or WorkloadGroup) besides the usual ones
+    Azure,
+    // PostgreSQL's pg_dump --format=plain (or pg_dumpall)
+    PgDump,
+    // MySQL's (or MariaDB's) mysqldump
+    Mysql,
+    // SQLite's .dump command
+    Sqlite,
+    // Oracle SQL Developer's Database Export (DDL and INSERTs)
+    Oracle,
+    // any text, split at the lines that match --split-on
+    #[value(skip)]
+    Pattern,
+}
+
+/// whether `content` creates a PL/SQL unit (a package, procedure, ...)
+fn is_plsql(content: &str) -> bool {
+    const UNITS: &[&str] = &["PACKAGE", "PROCEDURE", "FUNCTION", "TRIGGER", "TYPE"];
+    content.lines()
+        .map(|line| line.trim_start().to_uppercase())
+        .filter(|line| line.starts_with("CREATE "))
+        .any(|line| line.split_whitespace().any(|word| UNITS.contains(&word)))
+}
+
+impl Dialect {
+    /// whether scripts are SSMS's (of some version), with `/****** Object:`
+    /// headers
+    pub fn is_ssms(self) -> bool {
+        matches!(self, Dialect::Ssms | Dialect::Ssms2000 | Dialect::Azure)
+    }
+
+    /// whether scripts are T-SQL, with batches separated by GO and the
+    /// database chosen by USE
+    pub fn has_batches(self) -> bool {
+        self.is_ssms()
+    }
+
+    /// why `content`, the last object in the input, is cut off, if it
+    /// clearly is; other checks (see `truncation`) are only made for
+    /// dialects with batches
+    pub fn truncated(self, content: &str) -> Option<&'static str> {
+        match self {
+            Dialect::Ssms
+            | Dialect::Ssms2000
+            | Dialect::Azure => None,
+            // pg_dump ends every dump with this comment
+            Dialect::PgDump => if content.contains("-- PostgreSQL database dump complete") {
+                None
+            } else {
+                Some("is not followed by pg_dump's \"dump complete\" comment")
+            },
+            // and mysqldump with this one, unless told to leave comments out
+            Dialect::Mysql  => if content.contains("-- Dump completed") {
+                None
+            } else {
+                Some("is not followed by mysqldump's \"dump completed\" comment")
+            },
+            // the dump's COMMIT ends the last object, so there is none left
+            // to check unless it's missing
+            Dialect::Sqlite => Some("is not followed by the dump's COMMIT"),
+            // exports have no end of their own, but a PL/SQL unit is ended
+            // by a / line
+            Dialect::Oracle => if is_plsql(content) && !content.lines().any(|line| line.trim() == "/") {
+                Some("is a PL/SQL unit without the / line that ends it")
+            } else {
+                None
+            },
+            // any text can end anywhere
+            Dialect::Pattern => None,
+        }
+    }
+}
@@ -0,0 +1,99 @@
+/*
+ * errors that can stop (part of) a split
+ */
+use crate::exit;
+use std::io;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SplitError {
+    #[error("file does not exist: {0}")]
+    InputNotFound(String),
+    #[error("failed to open {path}: {source}")]
+    OpenInput { path: String, source: io::Error },
+    #[error("invalid configuration file {path}: {message}")]
+    Config { path: String, message: String },
+    #[error("failed to read line {line}: {source}")]
+    Read { line: usize, source: io::Error },
+    #[error("file already exists: {0}")]
+    OutputExists(String),
+    #[error("failed to create {path}: {source}")]
+    Create { path: String, source: io::Error },
+    #[error("failed to write {path}: {source}")]
+    Write { path: String, source: io::Error },
+    #[error("failed to write zip file {path}: {source}")]
+    Zip { path: String, source: zip::result::ZipError },
+    #[error("failed to serialize {what}: {source}")]
+    Serialize { what: &'static str, source: serde_json::Error },
+    #[error("no objects were found in {0} (is it the right encoding and format?)")]
+    NoObjects(String),
+    #[error("{0} file(s) could not be written")]
+    WriteFailures(usize),
+    #[error("{dir} is out of date: {count} file(s) differ, are missing or are extra")]
+    Drift { dir: String, count: usize },
+    #[error("{server} differs from the input: {count} object(s) differ, are missing or are extra")]
+    LiveDrift { server: String, count: usize },
+    #[error("{0} appears more than once in the input")]
+    Duplicate(String),
+    #[error("the input looks truncated: {path} {reason}")]
+    Truncated { path: String, reason: &'static str },
+    #[error("{0}")]
+    Git(String),
+    #[error("invalid connection string: {0}")]
+    BadConnection(String),
+    #[error("{0}")]
+    Server(String),
+    #[error("line {line}: {reason}")]
+    BadHeader { line: usize, text: String, reason: String },
+}
+
+impl SplitError {
+    /// exit status to use when this error ends the run
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            SplitError::InputNotFound(_)
+            | SplitError::OpenInput { .. }
+            | SplitError::Read { .. }         => exit::NO_INPUT,
+            SplitError::OutputExists(_)
+            | SplitError::Config { .. }
+            | SplitError::BadConnection(_)    => exit::USAGE,
+            SplitError::Create { .. }
+            | SplitError::Write { .. }
+            | SplitError::Zip { .. }
+            | SplitError::WriteFailures(_)
+            | SplitError::Git(_)              => exit::OUTPUT,
+            SplitError::Serialize { .. }      => exit::INTERNAL,
+            SplitError::NoObjects(_)          => exit::NO_OBJECTS,
+            SplitError::Drift { .. }
+            | SplitError::LiveDrift { .. }    => exit::DRIFT,
+            SplitError::BadHeader { .. }      => exit::BAD_HEADER,
+            SplitError::Duplicate(_)          => exit::DUPLICATE,
+            SplitError::Truncated { .. }      => exit::TRUNCATED,
+            SplitError::Server(_)             => exit::SERVER,
+        }
+    }
+
+    /// the input line the error is about, if any
+    pub fn line(&self) -> Option<(usize, &str)> {
+        match self {
+            SplitError::BadHeader { line, text, .. } => Some((*line, text)),
+            _                                        => None,
+        }
+    }
+}
+
+/// shorthand for wrapping io errors with the path they relate to
+pub trait IoContext<T> {
+    fn create_context(self, path: &str) -> Result<T, SplitError>;
+    fn write_context(self, path: &str) -> Result<T, SplitError>;
+}
+
+impl<T> IoContext<T> for io::Result<T> {
+    fn create_context(self, path: &str) -> Result<T, SplitError> {
+        self.map_err(|source| SplitError::Create { path: path.to_string(), source })
+    }
+
+    fn write_context(self, path: &str) -> Result<T, SplitError> {
+        self.map_err(|source| SplitError::Write { path: path.to_string(), source })
+    }
+}
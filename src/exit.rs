@@ -0,0 +1,43 @@
+/*
+ * exit codes, so that wrapper scripts can branch on the cause of a failure
+ */
+
+/// everything was split successfully
+pub const SUCCESS:    i32 = 0;
+/// invalid arguments or options
+pub const USAGE:      i32 = 1;
+/// the input file does not exist or couldn't be read
+pub const NO_INPUT:   i32 = 2;
+/// the input was read, but no objects were found in it
+pub const NO_OBJECTS: i32 = 3;
+/// some or all of the output couldn't be written
+pub const OUTPUT:     i32 = 4;
+/// an unexpected internal error
+pub const INTERNAL:   i32 = 5;
+/// `check` found differences between the input and the split directory (or
+/// `diff-live` between the input and the database)
+pub const DRIFT:      i32 = 6;
+/// an object header couldn't be parsed or named an unknown type (--strict)
+pub const BAD_HEADER: i32 = 7;
+/// an object appeared more than once in the input (--on-duplicate error)
+pub const DUPLICATE:  i32 = 8;
+/// the input seems to end in the middle of an object (--strict)
+pub const TRUNCATED:  i32 = 9;
+/// the server couldn't be connected to, or a query on it (or a file run by
+/// apply) failed
+pub const SERVER:     i32 = 10;
+
+/// shown at the end of --help
+pub const HELP: &str = "\
+Exit codes:
+  0  success
+  1  usage error (invalid arguments or options)
+  2  input file not found or unreadable
+  3  no objects were found in the input (only with --require-objects)
+  4  output could not be (fully) written
+  5  unexpected internal error
+  6  the split directory (or database) differs from the input (check and diff-live only)
+  7  an object header couldn't be parsed or named an unknown type (only with --strict)
+  8  an object appeared more than once in the input (only with --on-duplicate error)
+  9  the input seems to end in the middle of an object (only with --strict)
+ 10  the server couldn't be connected to, or a query on it failed (script, apply and diff-live only)";
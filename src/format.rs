@@ -0,0 +1,47 @@
+/*
+ * consistent formatting of object scripts (--format)
+ */
+use crate::tokenizer::batches;
+use regex::Regex;
+use sqlformat::{ FormatOptions, Indent, QueryParams };
+
+pub struct Formatter {
+    options:        FormatOptions,
+    /// a session option like `SET NOCOUNT ON`, which the formatter breaks up
+    /// as if it were an UPDATE's SET clause
+    broken_options: Regex,
+}
+
+impl Formatter {
+    pub fn new() -> Self {
+        Formatter {
+            options:        FormatOptions {
+                indent:                Indent::Spaces(4),
+                uppercase:             false,
+                lines_between_queries: 1,
+            },
+            broken_options: Regex::new(r"(?im)^([ \t]*SET)\n[ \t]+(\w+(?:[ \t]*,[ \t]*\w+)*[ \t]+(?:ON|OFF)\b)")
+                .expect("error compiling SET option regular expression"),
+        }
+    }
+
+    /// `content`, an object's script, with every batch run through the
+    /// formatter; GO separators are kept as they are, and so is keyword case
+    pub fn format(&self, content: &str) -> String {
+        let newline = if content.contains("\r\n") { "\r\n" } else { "\n" };
+        let (batches, _) = batches(content);
+        let mut formatted = String::with_capacity(content.len());
+        for batch in batches {
+            if batch.sql.trim().is_empty() {
+                formatted.push_str(&batch.sql);
+            } else {
+                let sql = sqlformat::format(&batch.sql, &QueryParams::None, self.options);
+                let sql = self.broken_options.replace_all(&sql, "$1 $2");
+                formatted.push_str(&sql.trim_end().replace('\n', newline));
+                formatted.push_str(newline);
+            }
+            formatted.push_str(&batch.separator);
+        }
+        formatted
+    }
+}
@@ -0,0 +1,244 @@
+/*
+ * --git-commit: commit exactly the files a run wrote and deleted, e.g. for a
+ * nightly schema snapshot; --changed-only: leave the files that match the
+ * last commit alone
+ */
+use crate::error::SplitError;
+use git2::{ ErrorCode, Index, ObjectType, Oid, Repository, Tree, TreeWalkMode, TreeWalkResult };
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// the error for a failed git operation
+fn failed(what: &str, e: git2::Error) -> SplitError {
+    SplitError::Git(format!("{} failed: {}", what, e.message()))
+}
+
+/// the repository whose work tree `dir` is inside, and the path of `dir`
+/// relative to the top of the work tree (with `/` separators, empty at the
+/// top)
+fn work_tree(dir: &str) -> Result<(Repository, String), SplitError> {
+    let not_in_repository = || SplitError::Git(format!("{} is not inside a git repository", dir));
+    let repository = Repository::discover(dir).map_err(|_| not_in_repository())?;
+    let top = repository.workdir().ok_or_else(not_in_repository)?;
+    let (top, dir) = match (fs::canonicalize(top), fs::canonicalize(dir)) {
+        (Ok(top), Ok(dir)) => (top, dir),
+        _                  => return Err(not_in_repository()),
+    };
+    let prefix: Vec<String> = dir.strip_prefix(&top)
+        .map_err(|_| not_in_repository())?
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    Ok((repository, prefix.join("/")))
+}
+
+/// `path`, relative to the directory at `prefix`, relative to the top of the
+/// work tree
+fn in_work_tree(prefix: &str, path: &str) -> String {
+    let path = path.replace('\\', "/");
+    if prefix.is_empty() {
+        path
+    } else {
+        format!("{}/{}", prefix, path)
+    }
+}
+
+/// the tree of the commit HEAD points to; none if nothing has been committed
+/// yet
+fn head_tree(repository: &Repository) -> Result<Option<Tree<'_>>, SplitError> {
+    let head = match repository.head() {
+        Ok(head)                                                                      => head,
+        Err(e) if matches!(e.code(), ErrorCode::UnbornBranch | ErrorCode::NotFound) => return Ok(None),
+        Err(e)                                                                        => return Err(failed("reading HEAD", e)),
+    };
+    head.peel_to_tree().map(Some).map_err(|e| failed("reading HEAD", e))
+}
+
+/// the object id of the file at `path` (relative to the top of the work
+/// tree) in `tree`, if it's there
+fn blob_in(tree: Option<&Tree>, path: &str) -> Option<Oid> {
+    let entry = tree?.get_path(Path::new(path)).ok()?;
+    (entry.kind() == Some(ObjectType::Blob)).then(|| entry.id())
+}
+
+/// fail unless `dir` is inside a git work tree
+pub fn require_work_tree(dir: &str) -> Result<(), SplitError> {
+    work_tree(dir).map(|_| ())
+}
+
+/// the files committed in HEAD under a directory
+pub struct Committed {
+    dir:   String,
+    /// object id of every file, by its path relative to `dir`
+    blobs: HashMap<String, Oid>,
+}
+
+impl Committed {
+    /// the files committed under `dir`, which has to be inside a git work
+    /// tree; none if nothing has been committed yet
+    pub fn load(dir: &str) -> Result<Self, SplitError> {
+        let (repository, prefix) = work_tree(dir)?;
+        let mut blobs = HashMap::new();
+        let tree = head_tree(&repository)?;
+        // paths are relative to (and limited to) dir
+        let subtree = match (tree, prefix.is_empty()) {
+            (Some(tree), true)  => Some(tree),
+            (Some(tree), false) => tree.get_path(Path::new(&prefix)).ok()
+                .and_then(|entry| entry.to_object(&repository).ok())
+                .and_then(|object| object.into_tree().ok()),
+            (None, _)           => None,
+        };
+        if let Some(subtree) = subtree {
+            subtree.walk(TreeWalkMode::PreOrder, |root, entry| {
+                // files, not links or submodules
+                if entry.kind() == Some(ObjectType::Blob) && [0o100644, 0o100755].contains(&entry.filemode()) {
+                    if let Some(name) = entry.name() {
+                        blobs.insert(format!("{}{}", root, name), entry.id());
+                    }
+                }
+                TreeWalkResult::Ok
+            }).map_err(|e| failed("reading HEAD", e))?;
+        }
+        Ok(Committed { dir: dir.to_string(), blobs })
+    }
+
+    /// whether the file at `relative_path` is in the work tree and was
+    /// committed with exactly `contents`
+    pub fn unchanged(&self, relative_path: &str, contents: &[u8]) -> bool {
+        let id = match self.blobs.get(relative_path) {
+            Some(id) => id,
+            None     => return false,
+        };
+        // git's object id of a file: the hash of a header and its contents
+        Oid::hash_object(ObjectType::Blob, contents).is_ok_and(|hash| hash == *id)
+            && Path::new(&self.dir).join(relative_path).is_file()
+    }
+}
+
+/// stage `paths` (relative to `dir`, which has to be inside a git work tree),
+/// whether they were written or deleted, and commit them with `message`;
+/// anything else that is staged is left alone. Returns false if none of them
+/// changed, in which case there's nothing to commit
+pub fn commit(dir: &str, paths: &[&str], message: &str) -> Result<bool, SplitError> {
+    let (repository, prefix) = work_tree(dir)?;
+    let top = repository.workdir().map(Path::to_path_buf).unwrap_or_default();
+    let paths: Vec<String> = paths.iter().map(|path| in_work_tree(&prefix, path)).collect();
+
+    // adds new and changed files and removes deleted ones
+    let mut index = repository.index().map_err(|e| failed("reading the index", e))?;
+    for path in paths.iter() {
+        let staged = if top.join(path).is_file() {
+            index.add_path(Path::new(path))
+        } else {
+            index.remove_path(Path::new(path))
+        };
+        staged.map_err(|e| failed(&format!("staging {}", path), e))?;
+    }
+    index.write().map_err(|e| failed("writing the index", e))?;
+
+    let tree = head_tree(&repository)?;
+    let changed: Vec<&String> = paths.iter()
+        .filter(|path| blob_in(tree.as_ref(), path) != index.get_path(Path::new(path.as_str()), 0).map(|entry| entry.id))
+        .collect();
+    if changed.is_empty() {
+        return Ok(false);
+    }
+
+    // the commit is HEAD with only these files changed, as staged
+    let mut committed = Index::new().map_err(|e| failed("committing", e))?;
+    if let Some(tree) = tree.as_ref() {
+        committed.read_tree(tree).map_err(|e| failed("committing", e))?;
+    }
+    for path in changed {
+        let staged = match index.get_path(Path::new(path.as_str()), 0) {
+            Some(entry) => committed.add(&entry),
+            None        => committed.remove(Path::new(path.as_str()), 0),
+        };
+        staged.map_err(|e| failed("committing", e))?;
+    }
+    let tree_id = committed.write_tree_to(&repository).map_err(|e| failed("committing", e))?;
+    let new_tree = repository.find_tree(tree_id).map_err(|e| failed("committing", e))?;
+    let signature = repository.signature()
+        .map_err(|e| failed("committing (set user.name and user.email)", e))?;
+    let parent = match tree {
+        Some(_) => Some(repository.head()
+            .and_then(|head| head.peel_to_commit())
+            .map_err(|e| failed("reading HEAD", e))?),
+        None    => None,
+    };
+    let parents: Vec<_> = parent.iter().collect();
+    repository.commit(Some("HEAD"), &signature, &signature, message, &new_tree, &parents)
+        .map_err(|e| failed("committing", e))?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Scratch;
+
+    /// the paths and contents of the files in HEAD
+    fn head_files(repository: &Repository) -> Vec<(String, String)> {
+        let tree = head_tree(repository).unwrap().unwrap();
+        let mut files = Vec::new();
+        tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+            if let Ok(blob) = entry.to_object(repository).and_then(|object| object.peel_to_blob()) {
+                files.push((format!("{}{}", root, entry.name().unwrap()), String::from_utf8_lossy(blob.content()).into_owned()));
+            }
+            TreeWalkResult::Ok
+        }).unwrap();
+        files
+    }
+
+    #[test]
+    fn only_the_given_files_are_committed() {
+        let scratch = Scratch::new("test-git-commit");
+        let repository = Repository::init(&scratch.0).unwrap();
+        let mut config = repository.config().unwrap();
+        config.set_str("user.name", "Snapshot").unwrap();
+        config.set_str("user.email", "snapshot@example.com").unwrap();
+        let out = scratch.0.join("db");
+        fs::create_dir_all(out.join("View")).unwrap();
+        fs::write(out.join("View/dbo.v.sql"), "CREATE VIEW v AS SELECT 1\n").unwrap();
+        fs::write(out.join("View/dbo.old.sql"), "CREATE VIEW old AS SELECT 0\n").unwrap();
+        fs::write(scratch.0.join("notes.txt"), "not the splitter's\n").unwrap();
+        let dir = out.display().to_string();
+
+        assert!(commit(&dir, &["View/dbo.v.sql", "View/dbo.old.sql"], "first").unwrap());
+        assert_eq!(head_files(&repository), [
+            (String::from("db/View/dbo.old.sql"), String::from("CREATE VIEW old AS SELECT 0\n")),
+            (String::from("db/View/dbo.v.sql"), String::from("CREATE VIEW v AS SELECT 1\n")),
+        ]);
+        let committed = Committed::load(&dir).unwrap();
+        assert!(committed.unchanged("View/dbo.v.sql", b"CREATE VIEW v AS SELECT 1\n"));
+        assert!(!committed.unchanged("View/dbo.v.sql", b"CREATE VIEW v AS SELECT 2\n"));
+
+        // nothing changed
+        assert!(!commit(&dir, &["View/dbo.v.sql"], "again").unwrap());
+
+        // a deleted file is committed as deleted, and what else is staged
+        // stays staged
+        fs::remove_file(out.join("View/dbo.old.sql")).unwrap();
+        let mut index = repository.index().unwrap();
+        index.add_path(Path::new("notes.txt")).unwrap();
+        index.write().unwrap();
+        assert!(commit(&dir, &["View/dbo.old.sql"], "second").unwrap());
+        assert_eq!(head_files(&repository), [
+            (String::from("db/View/dbo.v.sql"), String::from("CREATE VIEW v AS SELECT 1\n")),
+        ]);
+        assert_eq!(repository.head().unwrap().peel_to_commit().unwrap().message(), Some("second"));
+        assert!(repository.index().unwrap().get_path(Path::new("notes.txt"), 0).is_some());
+    }
+
+    #[test]
+    fn directories_outside_a_repository_are_refused() {
+        let scratch = Scratch::new("test-git-outside");
+        fs::create_dir_all(&scratch.0).unwrap();
+        let dir = scratch.0.display().to_string();
+        // unless the temporary directory itself is in a repository
+        if Repository::discover(&dir).is_err() {
+            assert!(require_work_tree(&dir).is_err());
+        }
+    }
+}
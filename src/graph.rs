@@ -0,0 +1,76 @@
+/*
+ * the dependencies between the objects of the input as a graph, e.g. to see
+ * what a change to a table affects
+ */
+use crate::cli::Cli;
+use crate::dependencies::Dependencies;
+use crate::diagnostics::Diagnostics;
+use crate::error::{ IoContext, SplitError };
+use crate::splitter::{ Scanner, open_input };
+use crate::summary::Summary;
+use clap::ValueEnum;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum GraphFormat {
+    // Graphviz
+    Dot,
+}
+
+/// `s` as a quoted DOT identifier
+fn quoted(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// a DOT digraph of `graph`, with an edge from every file to the files it
+/// depends on and the files grouped by their folder (i.e. type)
+fn dot(graph: &[(&str, Vec<&str>)]) -> String {
+    let mut folders: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (path, _) in graph {
+        let (folder, _) = path.rsplit_once('/').unwrap_or(("", path));
+        folders.entry(folder).or_default().push(path);
+    }
+
+    let mut out = String::from("digraph dependencies {\n    rankdir=LR;\n    node [shape=box];\n");
+    for (index, (folder, paths)) in folders.iter().enumerate() {
+        out.push_str(&format!("    subgraph cluster_{} {{\n        label={};\n", index, quoted(folder)));
+        for path in paths {
+            let (_, file) = path.rsplit_once('/').unwrap_or(("", path));
+            let label = file.strip_suffix(".sql").unwrap_or(file);
+            out.push_str(&format!("        {} [label={}];\n", quoted(path), quoted(label)));
+        }
+        out.push_str("    }\n");
+    }
+    for (path, dependencies) in graph {
+        for dependency in dependencies {
+            out.push_str(&format!("    {} -> {};\n", quoted(path), quoted(dependency)));
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// print the graph of the objects in the input described by `cli` to stdout
+pub fn graph(cli: &Cli, format: GraphFormat, diagnostics: &mut Diagnostics) -> Result<(), SplitError> {
+    let (mut reader, progress) = open_input(cli)?;
+    diagnostics.attach(&progress);
+    diagnostics.install_panic_hook();
+
+    let mut summary = Summary::new();
+    let mut scanner = Scanner::new(cli, diagnostics, progress.clone(), ".", None)?;
+    let mut dependencies = Dependencies::new();
+    scanner.scan(&mut reader, &mut summary, |object, _| {
+        dependencies.add(&object.record, &object.relative_path, &object.content);
+        Ok(())
+    })?;
+    progress.finish_and_clear();
+
+    let text = match format {
+        GraphFormat::Dot => dot(&dependencies.graph()),
+    };
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(text.as_bytes())
+        .and_then(|_| stdout.flush())
+        .write_context("<stdout>")
+}
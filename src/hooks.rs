@@ -0,0 +1,82 @@
+/*
+ * external commands run for every file written (--exec)
+ */
+use std::collections::VecDeque;
+use std::process::{ Child, Command };
+
+/// a command line that is run once per file, with `{}` replaced by the
+/// file's path; at most `jobs` commands run at the same time
+pub struct Hooks {
+    command:  String,
+    jobs:     usize,
+    running:  VecDeque<(String, Child)>,
+    /// description of every hook that failed
+    failures: Vec<String>,
+}
+
+/// quote `s` so the shell passes it to the command as a single argument
+fn shell_quote(s: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
+
+impl Hooks {
+    pub fn new(command: &str, jobs: usize) -> Self {
+        Hooks {
+            command:  command.to_string(),
+            jobs:     jobs.max(1),
+            running:  VecDeque::new(),
+            failures: Vec::new(),
+        }
+    }
+
+    /// the command line for `path`; the path is appended if the command
+    /// doesn't contain `{}`
+    fn command_line(&self, path: &str) -> String {
+        let quoted = shell_quote(path);
+        if self.command.contains("{}") {
+            self.command.replace("{}", &quoted)
+        } else {
+            format!("{} {}", self.command, quoted)
+        }
+    }
+
+    /// start the hook for `path`, first waiting for a running one to finish
+    /// if there are already too many
+    pub fn run(&mut self, path: &str) {
+        while self.running.len() >= self.jobs {
+            self.wait_oldest();
+        }
+        let line = self.command_line(path);
+        let spawned = if cfg!(windows) {
+            Command::new("cmd").arg("/C").arg(&line).spawn()
+        } else {
+            Command::new("sh").arg("-c").arg(&line).spawn()
+        };
+        match spawned {
+            Ok(child) => self.running.push_back((path.to_string(), child)),
+            Err(e)    => self.failures.push(format!("{}: failed to run hook: {}", path, e)),
+        }
+    }
+
+    fn wait_oldest(&mut self) {
+        if let Some((path, mut child)) = self.running.pop_front() {
+            match child.wait() {
+                Ok(status) if status.success() => (),
+                Ok(status) => self.failures.push(format!("{}: hook failed ({})", path, status)),
+                Err(e)     => self.failures.push(format!("{}: hook failed: {}", path, e)),
+            }
+        }
+    }
+
+    /// wait for every running hook; returns the failures
+    pub fn finish(mut self) -> Vec<String> {
+        while !self.running.is_empty() {
+            self.wait_oldest();
+        }
+        self.failures
+    }
+}
@@ -0,0 +1,299 @@
+/*
+ * existence checks that make object scripts safe to run more than once
+ * (--idempotent)
+ *
+ * procedures, functions, views and triggers get the classic stub pattern: a
+ * placeholder is created if the object doesn't exist yet, and the definition
+ * itself becomes an ALTER. other objects that can't be altered are only
+ * created if they don't exist yet, and so are the constraints, defaults,
+ * indexes and statistics that are added to tables after they're created.
+ */
+use crate::object::quoted_name;
+use crate::record::ObjectRecord;
+use crate::tokenizer::{ code_ranges, is_in_code, replace_first_in_code };
+use regex::{ Captures, Regex };
+
+pub struct Idempotent {
+    /// the CREATE of a procedure, function, view or trigger
+    create_module:  Regex,
+    /// the CREATE of any other object type handled here
+    create_other:   Regex,
+    /// an inline table-valued function
+    returns_table:  Regex,
+    /// a multi-statement table-valued function
+    returns_tvar:   Regex,
+    /// what a trigger is on, and whether it's an INSTEAD OF trigger
+    trigger_target: Regex,
+    /// an ALTER TABLE that adds a (named) constraint or an (unnamed) default
+    add_constraint: Regex,
+    /// the CREATE of an index or statistics on a table
+    create_index:   Regex,
+}
+
+/// `s` as the contents of an N'...' literal
+fn literal(s: &str) -> String {
+    s.replace('\'', "''")
+}
+
+/// `name` without the brackets around it, if any
+fn unquote(name: &str) -> String {
+    match name.strip_prefix('[').and_then(|n| n.strip_suffix(']')) {
+        Some(inner) => inner.replace("]]", "]"),
+        None        => name.to_string(),
+    }
+}
+
+impl Idempotent {
+    pub fn new() -> Self {
+        let name = r"(?:\[(?:[^\]]|\]\])+\]|\w+)";
+        // [schema.]table
+        let table = format!(r"(?P<table>(?:(?P<schema>{name})\s*\.\s*)?{name})");
+        Idempotent {
+            create_module:  Regex::new(r"(?im)^([ \t]*)CREATE(\s+)(PROC|PROCEDURE|FUNCTION|VIEW|TRIGGER)\b")
+                .expect("error compiling CREATE regular expression"),
+            create_other:   Regex::new(r"(?im)^[ \t]*CREATE\s+(TABLE|SEQUENCE|SYNONYM|TYPE|USER|ROLE|AGGREGATE|ASSEMBLY|COLUMN\s+(MASTER|ENCRYPTION)\s+KEY|EXTERNAL\s+(DATA\s+SOURCE|FILE\s+FORMAT|TABLE)|SECURITY\s+POLICY|MESSAGE\s+TYPE|CONTRACT|QUEUE|SERVICE|ROUTE|LOGIN|APPLICATION\s+ROLE|CERTIFICATE|(ASYMMETRIC|SYMMETRIC)\s+KEY|PARTITION\s+(FUNCTION|SCHEME)|XML\s+SCHEMA\s+COLLECTION)\b")
+                .expect("error compiling CREATE regular expression"),
+            returns_table:  Regex::new(r"(?is)\bRETURNS\s+TABLE\b")
+                .expect("error compiling RETURNS regular expression"),
+            returns_tvar:   Regex::new(r"(?is)\bRETURNS\s+@\w+\s+TABLE\b")
+                .expect("error compiling RETURNS regular expression"),
+            trigger_target: Regex::new(r"(?is)\bCREATE\s+TRIGGER\s+\S+\s+ON\s+(\S+)\s+(INSTEAD\s+OF)?")
+                .expect("error compiling trigger regular expression"),
+            add_constraint: Regex::new(&format!(r"(?im)^[ \t]*ALTER\s+TABLE\s+{table}\s+(?:WITH\s+(?:NO)?CHECK\s+)?ADD\s+(?:CONSTRAINT\s+(?P<constraint>{name})|DEFAULT\b[^\r\n]*?\bFOR\s+(?P<column>{name}))"))
+                .expect("error compiling ALTER TABLE regular expression"),
+            create_index:   Regex::new(&format!(r"(?im)^[ \t]*CREATE\s+(?:UNIQUE\s+)?(?:(?:NON)?CLUSTERED\s+)?(?:COLUMNSTORE\s+|PRIMARY\s+XML\s+|XML\s+|SPATIAL\s+)?(?P<kind>INDEX|STATISTICS)\s+(?P<name>{name})\s+ON\s+{table}"))
+                .expect("error compiling CREATE INDEX regular expression"),
+        }
+    }
+
+    /// placeholder definition for a procedure, function, view or trigger, or
+    /// None if `record`'s object isn't one (or it can't be determined)
+    fn stub(&self, record: &ObjectRecord, name: &str, content: &str) -> Option<String> {
+        match record.object_type.as_str() {
+            "StoredProcedure"     => Some(format!("CREATE PROCEDURE {} AS RETURN 0", name)),
+            "View"                => Some(format!("CREATE VIEW {} AS SELECT 1 AS stub", name)),
+            "UserDefinedFunction" => {
+                // the stub has to be the same kind of function, since ALTER
+                // can't change that
+                if self.returns_tvar.is_match(content) {
+                    Some(format!("CREATE FUNCTION {}() RETURNS @stub TABLE (stub int) AS BEGIN RETURN END", name))
+                } else if self.returns_table.is_match(content) {
+                    Some(format!("CREATE FUNCTION {}() RETURNS TABLE AS RETURN SELECT 1 AS stub", name))
+                } else {
+                    Some(format!("CREATE FUNCTION {}() RETURNS int AS BEGIN RETURN 0 END", name))
+                }
+            },
+            "Trigger"             => {
+                let caps = self.trigger_target.captures(content)?;
+                let timing = if caps.get(2).is_some() { "INSTEAD OF" } else { "AFTER" };
+                Some(format!("CREATE TRIGGER {} ON {} {} INSERT AS RETURN",
+                    name, &caps[1], timing))
+            },
+            _                     => None,
+        }
+    }
+
+    /// the condition under which `record`'s object (one that can't be
+    /// altered) doesn't exist yet
+    fn missing(&self, record: &ObjectRecord, name: &str) -> Option<String> {
+        let name = literal(name);
+        match record.object_type.as_str() {
+            "AsymmetricKey"          => Some(format!("ASYMKEY_ID(N'{}') IS NULL", literal(&record.name))),
+            "Certificate"            => Some(format!("CERT_ID(N'{}') IS NULL", literal(&record.name))),
+            "ColumnEncryptionKey"    => Some(format!("NOT EXISTS (SELECT * FROM sys.column_encryption_keys WHERE name = N'{}')", literal(&record.name))),
+            "ColumnMasterKey"        => Some(format!("NOT EXISTS (SELECT * FROM sys.column_master_keys WHERE name = N'{}')", literal(&record.name))),
+            "SymmetricKey"           => Some(format!("KEY_ID(N'{}') IS NULL", literal(&record.name))),
+            "Assembly"               => Some(format!("NOT EXISTS (SELECT * FROM sys.assemblies WHERE name = N'{}')", literal(&record.name))),
+            "PartitionFunction"      => Some(format!("NOT EXISTS (SELECT * FROM sys.partition_functions WHERE name = N'{}')", literal(&record.name))),
+            "PartitionScheme"        => Some(format!("NOT EXISTS (SELECT * FROM sys.partition_schemes WHERE name = N'{}')", literal(&record.name))),
+            "Table"                  => Some(format!("OBJECT_ID(N'{}', N'U') IS NULL", name)),
+            "SecurityPolicy"         => Some(format!("OBJECT_ID(N'{}', N'SP') IS NULL", name)),
+            "Sequence"               => Some(format!("OBJECT_ID(N'{}', N'SO') IS NULL", name)),
+            "Synonym"                => Some(format!("OBJECT_ID(N'{}', N'SN') IS NULL", name)),
+            "UserDefinedAggregate"   => Some(format!("OBJECT_ID(N'{}', N'AF') IS NULL", name)),
+            "UserDefinedDataType"
+            | "UserDefinedTableType" => Some(format!("TYPE_ID(N'{}') IS NULL", name)),
+            "ExternalDataSource"     => Some(format!("NOT EXISTS (SELECT * FROM sys.external_data_sources WHERE name = N'{}')", literal(&record.name))),
+            "ExternalFileFormat"     => Some(format!("NOT EXISTS (SELECT * FROM sys.external_file_formats WHERE name = N'{}')", literal(&record.name))),
+            "ExternalTable"          => Some(format!("OBJECT_ID(N'{}') IS NULL", name)),
+            "MessageType"            => Some(format!("NOT EXISTS (SELECT * FROM sys.service_message_types WHERE name = N'{}')", literal(&record.name))),
+            "Contract"               => Some(format!("NOT EXISTS (SELECT * FROM sys.service_contracts WHERE name = N'{}')", literal(&record.name))),
+            "Queue"                  => Some(format!("OBJECT_ID(N'{}', N'SQ') IS NULL", name)),
+            "Service"                => Some(format!("NOT EXISTS (SELECT * FROM sys.services WHERE name = N'{}')", literal(&record.name))),
+            "Route"                  => Some(format!("NOT EXISTS (SELECT * FROM sys.routes WHERE name = N'{}')", literal(&record.name))),
+            "Login"                  => Some(format!("SUSER_ID(N'{}') IS NULL", literal(&record.name))),
+            "User"
+            | "DatabaseRole"
+            | "ApplicationRole"      => Some(format!("DATABASE_PRINCIPAL_ID(N'{}') IS NULL", literal(&record.name))),
+            "XmlSchemaCollection"    => Some(format!(
+                "NOT EXISTS (SELECT * FROM sys.xml_schema_collections WHERE name = N'{}' AND schema_id = SCHEMA_ID(N'{}'))",
+                literal(&record.name), literal(&record.schema))),
+            _                        => None,
+        }
+    }
+
+    /// `content` with every constraint, default, index and statistics that
+    /// it adds to a table only added if it doesn't exist yet
+    fn guard_additions(&self, content: String) -> String {
+        let code = code_ranges(&content);
+        let in_code = |caps: &Captures| is_in_code(&code, caps.get(0).unwrap().start());
+        let mut guards: Vec<(usize, String)> = Vec::new();
+        for caps in self.add_constraint.captures_iter(&content).filter(in_code) {
+            let table = literal(&caps["table"]);
+            let guard = match (caps.name("constraint"), caps.name("column")) {
+                // constraints are named in the table's schema
+                (Some(constraint), _) => {
+                    let schema = caps.name("schema").map_or(String::new(), |schema| format!("{}.", schema.as_str()));
+                    format!("IF OBJECT_ID(N'{}{}') IS NULL\n", literal(&schema), literal(constraint.as_str()))
+                },
+                (None, Some(column)) => format!(
+                    "IF NOT EXISTS (SELECT * FROM sys.default_constraints WHERE parent_object_id = OBJECT_ID(N'{}') AND COL_NAME(parent_object_id, parent_column_id) = N'{}')\n",
+                    table, literal(&unquote(column.as_str()))),
+                _ => continue,
+            };
+            guards.push((caps.get(0).unwrap().start(), guard));
+        }
+        for caps in self.create_index.captures_iter(&content).filter(in_code) {
+            let catalog = if caps["kind"].eq_ignore_ascii_case("INDEX") { "sys.indexes" } else { "sys.stats" };
+            guards.push((caps.get(0).unwrap().start(), format!(
+                "IF NOT EXISTS (SELECT * FROM {} WHERE object_id = OBJECT_ID(N'{}') AND name = N'{}')\n",
+                catalog, literal(&caps["table"]), literal(&unquote(&caps["name"])))));
+        }
+        guards.sort_by_key(|(at, _)| *at);
+        let mut content = content;
+        for (at, guard) in guards.into_iter().rev() {
+            content.insert_str(at, &guard);
+        }
+        content
+    }
+
+    /// make `content`, the script for `record`'s object, rerunnable; a stub
+    /// batch is inserted at `at` (i.e. before the definition) if needed
+    pub fn apply(&self, record: &ObjectRecord, content: String, at: usize) -> String {
+        let name = quoted_name(&record.schema, &record.name);
+        let mut content = content;
+
+        if let Some(stub) = self.stub(record, &name, &content) {
+            content = match replace_first_in_code(&self.create_module, &content, "${1}ALTER$2$3") {
+                Some(altered) => altered,
+                None          => return content,
+            };
+            content.insert_str(at, &format!(
+                "IF OBJECT_ID(N'{}') IS NULL\n    EXEC(N'{}')\nGO\n",
+                literal(&name), literal(&stub)));
+            return content;
+        }
+        if let Some(missing) = self.missing(record, &name) {
+            let code = code_ranges(&content);
+            let create = self.create_other.find_iter(&content)
+                .find(|create| is_in_code(&code, create.start()));
+            if let Some(create) = create {
+                content.insert_str(create.start(), &format!("IF {}\n", missing));
+            }
+        }
+        // e.g. the constraints and indexes scripted after a table
+        self.guard_additions(content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer;
+
+    fn record(object_type: &str, name: &str) -> ObjectRecord {
+        ObjectRecord::new(String::from(object_type), String::from("dbo"), String::from(name),
+            String::from("Sales"), format!("{}/dbo.{}.sql", object_type, name), 0)
+    }
+
+    /// the batches of `content` that would fail if it were run a second time,
+    /// i.e. the ones that create or add something without checking first
+    fn unguarded(content: &str) -> Vec<String> {
+        let (batches, _) = tokenizer::batches(content);
+        batches.into_iter()
+            .map(|batch| batch.sql.trim().to_string())
+            .filter(|sql| {
+                let first = sql.lines()
+                    .find(|line| !line.trim().is_empty() && !line.trim_start().starts_with("/*"))
+                    .unwrap_or("")
+                    .trim_start()
+                    .to_ascii_uppercase();
+                first.starts_with("CREATE") || (first.starts_with("ALTER TABLE") && first.contains(" ADD "))
+            })
+            .collect()
+    }
+
+    const TABLE: &str = "\
+CREATE TABLE [dbo].[T] (a int, u int)
+GO
+ALTER TABLE [dbo].[T] ADD  DEFAULT ((0)) FOR [a]
+GO
+ALTER TABLE [dbo].[T] ADD  CONSTRAINT [DF_T_u] DEFAULT ((1)) FOR [u]
+GO
+ALTER TABLE [dbo].[T]  WITH CHECK ADD  CONSTRAINT [FK_T_U] FOREIGN KEY([u])
+REFERENCES [dbo].[U] ([id])
+GO
+ALTER TABLE [dbo].[T] CHECK CONSTRAINT [FK_T_U]
+GO
+CREATE NONCLUSTERED INDEX [IX_T_a] ON [dbo].[T]
+(
+	[a] ASC
+)
+GO
+CREATE STATISTICS [ST_T_u] ON [dbo].[T]([u])
+GO
+";
+
+    #[test]
+    fn everything_scripted_with_a_table_is_guarded() {
+        let header = "/****** Object:  Table [dbo].[T]    Script Date: 1/1/2024 ******/\n";
+        let content = Idempotent::new().apply(&record("Table", "T"), format!("{}{}", header, TABLE), header.len());
+        assert_eq!(unguarded(&content), Vec::<String>::new());
+        assert!(content.contains("IF OBJECT_ID(N'[dbo].[T]', N'U') IS NULL\nCREATE TABLE"));
+        assert!(content.contains("IF NOT EXISTS (SELECT * FROM sys.default_constraints WHERE parent_object_id = OBJECT_ID(N'[dbo].[T]') \
+            AND COL_NAME(parent_object_id, parent_column_id) = N'a')\nALTER TABLE [dbo].[T] ADD  DEFAULT"));
+        assert!(content.contains("IF OBJECT_ID(N'[dbo].[DF_T_u]') IS NULL\nALTER TABLE"));
+        assert!(content.contains("IF OBJECT_ID(N'[dbo].[FK_T_U]') IS NULL\nALTER TABLE"));
+        assert!(content.contains("IF NOT EXISTS (SELECT * FROM sys.indexes WHERE object_id = OBJECT_ID(N'[dbo].[T]') AND name = N'IX_T_a')\nCREATE NONCLUSTERED"));
+        assert!(content.contains("IF NOT EXISTS (SELECT * FROM sys.stats WHERE object_id = OBJECT_ID(N'[dbo].[T]') AND name = N'ST_T_u')\nCREATE STATISTICS"));
+        // checking (rather than adding) a constraint can be run any number of times
+        assert!(content.contains("GO\nALTER TABLE [dbo].[T] CHECK CONSTRAINT"));
+    }
+
+    #[test]
+    fn detached_constraints_and_indexes_are_guarded_without_a_header() {
+        let idempotent = Idempotent::new();
+        let constraints = TABLE.split_once("GO\n").unwrap().1;
+        let content = idempotent.apply(&record("Constraints", "T"), constraints.to_string(), 0);
+        assert_eq!(unguarded(&content), Vec::<String>::new());
+        let index = "CREATE UNIQUE CLUSTERED INDEX [IX_[x]]] ON [dbo].[U] ([a])\nGO\n";
+        let content = idempotent.apply(&record("Index", "IX_[x]"), index.to_string(), 0);
+        assert_eq!(content, format!("IF NOT EXISTS (SELECT * FROM sys.indexes WHERE object_id = OBJECT_ID(N'[dbo].[U]') AND name = N'IX_[x]')\n{}", index));
+    }
+
+    #[test]
+    fn modules_are_not_guarded_statement_by_statement() {
+        let procedure = "CREATE PROCEDURE [dbo].[p1] AS\nCREATE TABLE #t (a int)\nCREATE INDEX [IX_t] ON #t (a)\nGO\n";
+        let content = Idempotent::new().apply(&record("StoredProcedure", "p1"), procedure.to_string(), 0);
+        assert!(content.ends_with("ALTER PROCEDURE [dbo].[p1] AS\nCREATE TABLE #t (a int)\nCREATE INDEX [IX_t] ON #t (a)\nGO\n"));
+    }
+
+    #[test]
+    fn stubs_escape_brackets_in_names() {
+        let procedure = "CREATE PROCEDURE [dbo].[p]]1] AS SELECT 1\nGO\n";
+        let content = Idempotent::new().apply(&record("StoredProcedure", "p]1"), procedure.to_string(), 0);
+        assert_eq!(content, "IF OBJECT_ID(N'[dbo].[p]]1]') IS NULL\n    EXEC(N'CREATE PROCEDURE [dbo].[p]]1] AS RETURN 0')\nGO\n\
+            ALTER PROCEDURE [dbo].[p]]1] AS SELECT 1\nGO\n");
+    }
+
+    #[test]
+    fn creates_in_comments_and_strings_are_left_alone() {
+        let idempotent = Idempotent::new();
+        let procedure = "/* CREATE PROCEDURE [dbo].[p0] AS RETURN 0 */\n-- CREATE VIEW [dbo].[v]\nCREATE PROCEDURE [dbo].[p1] AS SELECT 1\nGO\n";
+        let content = idempotent.apply(&record("StoredProcedure", "p1"), procedure.to_string(), 0);
+        assert!(content.ends_with("/* CREATE PROCEDURE [dbo].[p0] AS RETURN 0 */\n-- CREATE VIEW [dbo].[v]\nALTER PROCEDURE [dbo].[p1] AS SELECT 1\nGO\n"));
+        let table = "/*\nCREATE TABLE [dbo].[T] (a int)\nCREATE INDEX [IX_old] ON [dbo].[T] (a)\n*/\nCREATE TABLE [dbo].[T] (a int, b int)\nGO\n";
+        let content = idempotent.apply(&record("Table", "T"), table.to_string(), 0);
+        assert_eq!(content, table.replace("*/\nCREATE", "*/\nIF OBJECT_ID(N'[dbo].[T]', N'U') IS NULL\nCREATE"));
+    }
+}
@@ -0,0 +1,41 @@
+/*
+ * CSV inventory of every object written during a run
+ */
+use crate::error::{ IoContext, SplitError };
+use crate::output::Output;
+use crate::record::ObjectRecord;
+use std::fs::File;
+use std::io::{ BufWriter, Write };
+
+/// quote a CSV field if it contains a delimiter, quote or line break
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// write one row per object to the CSV file at `dest`
+pub fn write_inventory(
+    dest:    &str,
+    output:  &Output,
+    records: &[ObjectRecord],
+) -> Result<(), SplitError> {
+    let file = File::create(dest).create_context(dest)?;
+    let mut writer = BufWriter::new(file);
+    writeln!(writer, "database,type,schema,name,path,lines,bytes")
+        .write_context(dest)?;
+    for record in records {
+        writeln!(writer, "{},{},{},{},{},{},{}",
+            csv_field(&record.database),
+            csv_field(&record.object_type),
+            csv_field(&record.schema),
+            csv_field(&record.name),
+            csv_field(output.relative_path(&record.path)),
+            record.lines,
+            record.bytes)
+            .write_context(dest)?;
+    }
+    writer.flush().write_context(dest)
+}
@@ -0,0 +1,108 @@
+/*
+ * normalizing the case of T-SQL keywords (--keywords)
+ */
+use crate::tokenizer::Tokenizer;
+use clap::ValueEnum;
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum KeywordCase {
+    Upper,
+    Lower,
+    // leave keywords as they were scripted
+    Preserve,
+}
+
+/// T-SQL's reserved keywords, plus a few common ones that aren't reserved
+const KEYWORDS: &[&str] = &[
+    "ADD", "ALL", "ALTER", "AND", "ANY", "AS", "ASC", "AUTHORIZATION",
+    "BACKUP", "BEGIN", "BETWEEN", "BREAK", "BROWSE", "BULK", "BY", "CASCADE",
+    "CASE", "CATCH", "CHECK", "CHECKPOINT", "CLOSE", "CLUSTERED", "COALESCE",
+    "COLLATE", "COLUMN", "COMMIT", "COMPUTE", "CONSTRAINT", "CONTAINS",
+    "CONTAINSTABLE", "CONTINUE", "CONVERT", "CREATE", "CROSS", "CURRENT",
+    "CURRENT_DATE", "CURRENT_TIME", "CURRENT_TIMESTAMP", "CURRENT_USER",
+    "CURSOR", "DATABASE", "DBCC", "DEALLOCATE", "DECLARE", "DEFAULT",
+    "DELETE", "DENY", "DESC", "DISK", "DISTINCT", "DISTRIBUTED", "DOUBLE",
+    "DROP", "DUMP", "ELSE", "END", "ERRLVL", "ESCAPE", "EXCEPT", "EXEC",
+    "EXECUTE", "EXISTS", "EXIT", "EXTERNAL", "FETCH", "FILE", "FILLFACTOR",
+    "FOR", "FOREIGN", "FREETEXT", "FREETEXTTABLE", "FROM", "FULL",
+    "FUNCTION", "GOTO", "GRANT", "GROUP", "HAVING", "HOLDLOCK", "IDENTITY",
+    "IDENTITY_INSERT", "IDENTITYCOL", "IF", "IN", "INDEX", "INNER", "INSERT",
+    "INTERSECT", "INTO", "IS", "JOIN", "KEY", "KILL", "LEFT", "LIKE",
+    "LINENO", "LOAD", "MERGE", "NATIONAL", "NOCHECK", "NONCLUSTERED", "NOT",
+    "NULL", "NULLIF", "OF", "OFF", "OFFSETS", "ON", "OPEN", "OPENDATASOURCE",
+    "OPENQUERY", "OPENROWSET", "OPENXML", "OPTION", "OR", "ORDER", "OUT",
+    "OUTER", "OUTPUT", "OVER", "PERCENT", "PIVOT", "PLAN", "PRECISION",
+    "PRIMARY", "PRINT", "PROC", "PROCEDURE", "PUBLIC", "RAISERROR", "READ",
+    "READTEXT", "RECONFIGURE", "REFERENCES", "REPLICATION", "RESTORE",
+    "RESTRICT", "RETURN", "RETURNS", "REVERT", "REVOKE", "RIGHT", "ROLLBACK",
+    "ROWCOUNT", "ROWGUIDCOL", "RULE", "SAVE", "SCHEMA", "SECURITYAUDIT",
+    "SELECT", "SEMANTICKEYPHRASETABLE", "SEMANTICSIMILARITYDETAILSTABLE",
+    "SEMANTICSIMILARITYTABLE", "SESSION_USER", "SET", "SETUSER", "SHUTDOWN",
+    "SOME", "STATISTICS", "SYSTEM_USER", "TABLE", "TABLESAMPLE", "TEXTSIZE",
+    "THEN", "THROW", "TO", "TOP", "TRAN", "TRANSACTION", "TRIGGER",
+    "TRUNCATE", "TRY", "TRY_CONVERT", "TSEQUAL", "UNION", "UNIQUE",
+    "UNPIVOT", "UPDATE", "UPDATETEXT", "USE", "USER", "VALUES", "VARYING",
+    "VIEW", "WAITFOR", "WHEN", "WHERE", "WHILE", "WITH", "WITHIN",
+    "WRITETEXT",
+];
+
+/// whether `c` can be part of a word (including variables like @name and
+/// temporary tables like #name)
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '@' | '#' | '$')
+}
+
+pub struct Keywords {
+    case:     KeywordCase,
+    keywords: HashSet<&'static str>,
+}
+
+impl Keywords {
+    pub fn new(case: KeywordCase) -> Self {
+        Keywords {
+            case,
+            keywords: KEYWORDS.iter().copied().collect(),
+        }
+    }
+
+    /// `word` in the requested case if it's a keyword
+    fn word<'w>(&self, word: &'w str) -> Cow<'w, str> {
+        let upper = word.to_ascii_uppercase();
+        if !self.keywords.contains(upper.as_str()) {
+            return word.into();
+        }
+        match self.case {
+            KeywordCase::Upper    => upper.into(),
+            KeywordCase::Lower    => word.to_ascii_lowercase().into(),
+            KeywordCase::Preserve => word.into(),
+        }
+    }
+
+    /// `content` with every keyword outside of strings, quoted identifiers
+    /// and comments in the requested case
+    pub fn apply(&self, content: &str) -> String {
+        let mut tokenizer = Tokenizer::new();
+        let mut result = String::with_capacity(content.len());
+        for line in content.split_inclusive('\n') {
+            let mut copied = 0;
+            for range in tokenizer.code(line) {
+                result.push_str(&line[copied..range.start]);
+                let code = &line[range.clone()];
+                let mut rest = code;
+                while let Some(start) = rest.find(is_word) {
+                    result.push_str(&rest[..start]);
+                    let word = &rest[start..];
+                    let end = word.find(|c| !is_word(c)).unwrap_or(word.len());
+                    result.push_str(&self.word(&word[..end]));
+                    rest = &word[end..];
+                }
+                result.push_str(rest);
+                copied = range.end;
+            }
+            result.push_str(&line[copied..]);
+        }
+        result
+    }
+}
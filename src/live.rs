@@ -0,0 +1,140 @@
+/*
+ * the diff-live subcommand: compare the modules in a dump with their
+ * definitions in a database, to detect schema drift
+ */
+use crate::check::{ Drift, print_drift };
+use crate::cli::Cli;
+use crate::diagnostics::Diagnostics;
+use crate::error::SplitError;
+use crate::object::{ Layout, ObjectType, Placement };
+use crate::script::MODULES;
+use crate::server::Connection;
+use crate::splitter::{ Scanner, ScannedObject, open_input };
+use crate::summary::Summary;
+use crate::tokenizer::is_header;
+use indicatif::ProgressBar;
+use std::collections::{ HashMap, HashSet };
+use std::io::{ BufRead, Cursor };
+
+/// (type, schema, name), lowercased
+type Key = (String, String, String);
+
+struct Module {
+    key:        Key,
+    definition: String,
+    /// the path it would be split to
+    path:       String,
+}
+
+/// the types whose definitions the server keeps (see `script`)
+fn is_module(object_type: &str) -> bool {
+    [
+        ObjectType::StoredProcedure,
+        ObjectType::View,
+        ObjectType::UserDefinedFunction,
+        ObjectType::Trigger,
+        ObjectType::DdlTrigger,
+    ].iter().any(|t| t.to_string() == object_type)
+}
+
+fn key(object: &ScannedObject) -> Key {
+    let record = &object.record;
+    (record.object_type.to_owned(), record.schema.to_lowercase(), record.name.to_lowercase())
+}
+
+/// the statement(s) defining `object`, without its USE, header, SET options
+/// and GO separators, and with the same line endings and no trailing
+/// whitespace, so that scripts from SSMS and the server compare equal
+fn definition(object: &ScannedObject) -> String {
+    let mut lines: Vec<&str> = object.content[object.header_start..]
+        .lines()
+        .map(str::trim_end)
+        .skip_while(|line| !is_header(line.trim_start()))
+        .skip(1)
+        .skip_while(|line| {
+            let upper = line.trim().to_uppercase();
+            upper.is_empty()
+                || upper == "GO"
+                || upper.starts_with("SET ANSI_NULLS ")
+                || upper.starts_with("SET QUOTED_IDENTIFIER ")
+        })
+        .collect();
+    while lines.last().is_some_and(|line| line.is_empty() || line.eq_ignore_ascii_case("GO")) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/// the modules in `reader`, in the order they were read, and how many
+/// objects of other types there were
+fn modules(
+    cli:         &Cli,
+    diagnostics: &Diagnostics,
+    progress:    ProgressBar,
+    reader:      &mut dyn BufRead,
+) -> Result<(Vec<Module>, usize), SplitError> {
+    let mut summary = Summary::new();
+    let mut scanner = Scanner::new(cli, diagnostics, progress, ".", None)?;
+    let mut modules = Vec::new();
+    let mut others = 0;
+    scanner.scan(reader, &mut summary, |object, _| {
+        if is_module(&object.record.object_type) {
+            modules.push(Module {
+                key:        key(&object),
+                definition: definition(&object),
+                path:       object.relative_path.to_owned(),
+            });
+        } else {
+            others += 1;
+        }
+        Ok(())
+    })?;
+    Ok((modules, others))
+}
+
+/// compare the modules in the input described by `cli` with those in the
+/// database of `connection`, printing one line per difference to stdout
+pub fn diff_live(cli: &Cli, connection: &Connection, diagnostics: &mut Diagnostics) -> Result<(), SplitError> {
+    // every module gets its own file, like the server's script
+    let cli = Cli {
+        triggers: Placement::Folder,
+        layout:   Layout::Types,
+        ..cli.clone()
+    };
+    let (mut reader, progress) = open_input(&cli)?;
+    diagnostics.attach(&progress);
+    diagnostics.install_panic_hook();
+    let (dumped, others) = modules(&cli, diagnostics, progress.clone(), &mut reader)?;
+    progress.finish_and_clear();
+
+    let script = connection.query(MODULES)?;
+    let server_diagnostics = Diagnostics::new(&connection.describe(), !cli.no_color);
+    let (live, _) = modules(&cli, &server_diagnostics, ProgressBar::hidden(), &mut Cursor::new(script.into_bytes()))?;
+    let live: HashMap<&Key, &Module> = live.iter().map(|module| (&module.key, module)).collect();
+
+    let mut drift: Vec<(Drift, String)> = Vec::new();
+    let mut seen: HashSet<&Key> = HashSet::new();
+    for module in dumped.iter() {
+        seen.insert(&module.key);
+        match live.get(&module.key) {
+            None                                               => drift.push((Drift::Missing, module.path.to_owned())),
+            Some(live) if live.definition != module.definition => drift.push((Drift::Differs, module.path.to_owned())),
+            Some(_)                                            => (),
+        }
+    }
+    let mut extra: Vec<String> = live.iter()
+        .filter(|(key, _)| !seen.contains(*key))
+        .map(|(_, module)| module.path.to_owned())
+        .collect();
+    extra.sort();
+    drift.extend(extra.into_iter().map(|path| (Drift::Extra, path)));
+
+    print_drift(cli.quiet, &format!("{} module(s)", dumped.len()), &drift);
+    if others > 0 && !cli.quiet {
+        eprintln!("{} object(s) of other types weren't compared, since the server keeps no script of them", others);
+    }
+    if !drift.is_empty() {
+        return Err(SplitError::LiveDrift { server: connection.describe(), count: drift.len() });
+    }
+    Ok(())
+}
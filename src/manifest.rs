@@ -0,0 +1,38 @@
+/*
+ * manifest.json: an index of every object written during a run
+ */
+use crate::error::SplitError;
+use crate::output::Output;
+use crate::record::ObjectRecord;
+use serde::Serialize;
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Serialize)]
+struct Manifest {
+    /// every database the input USEs, in the order they first appear
+    databases: Vec<String>,
+    objects:   Vec<ObjectRecord>,
+}
+
+/// write manifest.json into the root of `output`, with each object's path
+/// given relative to the root
+pub fn write_manifest(output: &mut Output, records: &[ObjectRecord]) -> Result<(), SplitError> {
+    let mut databases: Vec<String> = Vec::new();
+    for record in records {
+        if !record.database.is_empty() && !databases.contains(&record.database) {
+            databases.push(record.database.to_owned());
+        }
+    }
+    let objects = records.iter()
+        .map(|record| {
+            let mut record = record.clone();
+            record.path = output.relative_path(&record.path).to_string();
+            record
+        })
+        .collect();
+    let mut json = serde_json::to_string_pretty(&Manifest { databases, objects })
+        .map_err(|source| SplitError::Serialize { what: "manifest", source })?;
+    json.push('\n');
+    output.write_root_file(MANIFEST_FILE, json.as_bytes())
+}
@@ -0,0 +1,92 @@
+/*
+ * output for migration tools (--output-format): files named the way the tool
+ * expects them, rather than a folder per object type, or a changelog listing
+ * them
+ */
+use crate::error::SplitError;
+use crate::output::Output;
+use crate::record::ObjectRecord;
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    // a folder per object type
+    Folders,
+    // V<version>__<Type>_<schema>_<name>.sql files, for Flyway
+    Flyway,
+    // a folder per object type and a Liquibase changelog
+    Liquibase,
+    // <type rank>_<number>_<Type>_<schema>_<name>.sql files, which DbUp runs
+    // in name order
+    Dbup,
+}
+
+/// `s` with the characters that separate the parts of a migration's name
+/// replaced by underscores
+fn description(s: &str) -> String {
+    s.replace(['.', ' ', '/'], "_")
+}
+
+/// the Flyway file name of an object of `object_type` whose file would
+/// otherwise be `relative_path`, e.g. `V12__Table_dbo_Orders.sql` for
+/// `Table/dbo.Orders.sql`
+pub fn flyway_name(version: u64, object_type: &str, relative_path: &str) -> String {
+    let file = relative_path.rsplit('/').next().unwrap_or(relative_path);
+    let stem = file.strip_suffix(".sql").unwrap_or(file);
+    format!("V{}__{}_{}.sql", version, description(object_type), description(stem))
+}
+
+/// the DbUp file name of an object of `object_type` whose file would otherwise
+/// be `relative_path`, e.g. `25_000012_Table_dbo_Orders.sql` for
+/// `Table/dbo.Orders.sql`: the type's place in a deployment comes first, so
+/// scripts are run by type and then in the order they were numbered
+pub fn dbup_name(rank: usize, number: u64, object_type: &str, relative_path: &str) -> String {
+    let file = relative_path.rsplit('/').next().unwrap_or(relative_path);
+    let stem = file.strip_suffix(".sql").unwrap_or(file);
+    format!("{:02}_{:06}_{}_{}.sql", rank, number, description(object_type), description(stem))
+}
+
+/// `s` with the characters that are special in XML escaped
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// write the Liquibase changelog `name` (XML, or YAML if it ends in .yaml or
+/// .yml) into the root of `output`, with a changeset for each of `files`
+/// (paths relative to the root, with their records) in that order; the
+/// SHA-256 of each file is in its changeset's comment
+pub fn write_changelog(output: &mut Output, name: &str, files: &[(String, &ObjectRecord)]) -> Result<(), SplitError> {
+    let mut contents = String::new();
+    if name.ends_with(".yaml") || name.ends_with(".yml") {
+        // JSON strings are valid YAML
+        let quoted = |s: &str| serde_json::to_string(s)
+            .map_err(|source| SplitError::Serialize { what: "changelog", source });
+        contents.push_str("databaseChangeLog:\n");
+        for (path, record) in files {
+            let id = path.strip_suffix(".sql").unwrap_or(path);
+            contents.push_str(&format!("  - changeSet:\n      id: {}\n      author: sql-splitter\n", quoted(id)?));
+            contents.push_str(&format!("      comment: {}\n", quoted(&format!("sha256:{}", record.sha256))?));
+            contents.push_str(&format!("      changes:\n        - sqlFile:\n            path: {}\n", quoted(path)?));
+            contents.push_str("            relativeToChangelogFile: true\n");
+        }
+    } else {
+        contents.push_str(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<databaseChangeLog\n",
+            "    xmlns=\"http://www.liquibase.org/xml/ns/dbchangelog\"\n",
+            "    xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\"\n",
+            "    xsi:schemaLocation=\"http://www.liquibase.org/xml/ns/dbchangelog http://www.liquibase.org/xml/ns/dbchangelog/dbchangelog-latest.xsd\">\n"));
+        for (path, record) in files {
+            let id = path.strip_suffix(".sql").unwrap_or(path);
+            contents.push_str(&format!("    <changeSet id=\"{}\" author=\"sql-splitter\">\n", xml_escape(id)));
+            contents.push_str(&format!("        <comment>sha256:{}</comment>\n", record.sha256));
+            contents.push_str(&format!("        <sqlFile path=\"{}\" relativeToChangelogFile=\"true\"/>\n", xml_escape(path)));
+            contents.push_str("    </changeSet>\n");
+        }
+        contents.push_str("</databaseChangeLog>\n");
+    }
+    output.write_root_file(name, contents.as_bytes())
+}
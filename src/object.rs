@@ -0,0 +1,1029 @@
+/*
+ * the kinds of database objects found in SSMS-generated scripts and how their
+ * headers are recognized
+ */
+use crate::config::CustomType;
+use crate::dialect::Dialect;
+use crate::tokenizer::starts_with_keyword;
+use clap::ValueEnum;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum ObjectType {
+    ApplicationRole,
+    Assembly,
+    AsymmetricKey,
+    Certificate,
+    ColumnEncryptionKey,
+    ColumnMasterKey,
+    /// a constraint or column default that is scripted on its own, as
+    /// pg_dump does
+    Constraint,
+    Contract,
+    /// INSERT batches of "schema and data" scripts, which have no header
+    Data,
+    Database,
+    DatabaseRole,
+    DdlTrigger,
+    Default,
+    /// sp_addextendedproperty batches, which are scripted without a header
+    ExtendedProperty,
+    ExternalDataSource,
+    ExternalFileFormat,
+    ExternalTable,
+    FullTextCatalog,
+    FullTextIndex,
+    FullTextStopList,
+    Index,
+    Login,
+    MessageType,
+    /// an Oracle package's specification
+    Package,
+    /// an Oracle package's body
+    PackageBody,
+    PartitionFunction,
+    PartitionScheme,
+    /// GRANT, DENY and REVOKE batches, which are scripted without a header
+    Permission,
+    PlanGuide,
+    Queue,
+    Route,
+    Rule,
+    Schema,
+    /// a part of a file split with --split-on, which goes in the output's
+    /// root
+    Section,
+    SecurityPolicy,
+    Sequence,
+    Service,
+    Statistic,
+    StoredProcedure,
+    SymmetricKey,
+    Synonym,
+    Table,
+    Trigger,
+    User,
+    UserDefinedAggregate,
+    UserDefinedDataType,
+    UserDefinedFunction,
+    UserDefinedTableType,
+    View,
+    XmlSchemaCollection,
+    /// a type declared in the configuration file
+    Custom(CustomType),
+    /// a type this tool doesn't know about
+    Other(String),
+}
+
+impl ObjectType {
+    /// every supported type, i.e. every folder this tool may create (apart
+    /// from those in Other/)
+    pub const ALL: &'static [ObjectType] = &[
+        ObjectType::ApplicationRole,
+        ObjectType::Assembly,
+        ObjectType::AsymmetricKey,
+        ObjectType::Certificate,
+        ObjectType::ColumnEncryptionKey,
+        ObjectType::ColumnMasterKey,
+        ObjectType::Constraint,
+        ObjectType::Contract,
+        ObjectType::Data,
+        ObjectType::Database,
+        ObjectType::DatabaseRole,
+        ObjectType::DdlTrigger,
+        ObjectType::Default,
+        ObjectType::ExtendedProperty,
+        ObjectType::ExternalDataSource,
+        ObjectType::ExternalFileFormat,
+        ObjectType::ExternalTable,
+        ObjectType::FullTextCatalog,
+        ObjectType::FullTextIndex,
+        ObjectType::FullTextStopList,
+        ObjectType::Index,
+        ObjectType::Login,
+        ObjectType::MessageType,
+        ObjectType::Package,
+        ObjectType::PackageBody,
+        ObjectType::PartitionFunction,
+        ObjectType::PartitionScheme,
+        ObjectType::Permission,
+        ObjectType::PlanGuide,
+        ObjectType::Queue,
+        ObjectType::Route,
+        ObjectType::Rule,
+        ObjectType::Schema,
+        ObjectType::SecurityPolicy,
+        ObjectType::Sequence,
+        ObjectType::Service,
+        ObjectType::Statistic,
+        ObjectType::StoredProcedure,
+        ObjectType::SymmetricKey,
+        ObjectType::Synonym,
+        ObjectType::Table,
+        ObjectType::Trigger,
+        ObjectType::User,
+        ObjectType::UserDefinedAggregate,
+        ObjectType::UserDefinedDataType,
+        ObjectType::UserDefinedFunction,
+        ObjectType::UserDefinedTableType,
+        ObjectType::View,
+        ObjectType::XmlSchemaCollection,
+    ];
+
+    /// the folder (relative to the output root) that objects of this type
+    /// are written to
+    pub fn folder(&self) -> String {
+        match self {
+            ObjectType::AsymmetricKey
+            | ObjectType::Certificate
+            | ObjectType::ColumnEncryptionKey
+            | ObjectType::ColumnMasterKey
+            | ObjectType::SymmetricKey   => format!("Security/{}", self),
+            ObjectType::Contract
+            | ObjectType::MessageType
+            | ObjectType::Queue
+            | ObjectType::Route
+            | ObjectType::Service        => format!("ServiceBroker/{}", self),
+            ObjectType::ExtendedProperty => String::from("ExtendedProperties"),
+            ObjectType::Section          => String::new(),
+            ObjectType::Permission       => String::from("Security/Permissions"),
+            ObjectType::Custom(custom)   => custom.folder.to_owned().unwrap_or_else(|| custom.header.to_owned()),
+            ObjectType::Other(name)      => format!("Other/{}", name),
+            _                            => self.to_string(),
+        }
+    }
+
+    /// the folder that objects of this type are written to in `layout`
+    pub fn folder_in(&self, layout: Layout) -> String {
+        if layout == Layout::Types {
+            return self.folder();
+        }
+        // Redgate SQL Source Control's names
+        let folder = match self {
+            ObjectType::ApplicationRole        => "Security/Roles",
+            ObjectType::Assembly               => "Assemblies",
+            ObjectType::AsymmetricKey          => "Security/Asymmetric Keys",
+            ObjectType::Certificate            => "Security/Certificates",
+            ObjectType::ColumnEncryptionKey    => "Security/Column Encryption Keys",
+            ObjectType::ColumnMasterKey        => "Security/Column Master Keys",
+            ObjectType::Contract               => "Service Broker/Contracts",
+            ObjectType::DatabaseRole           => "Security/Roles",
+            ObjectType::DdlTrigger             => "Database Triggers",
+            ObjectType::Default                => "Defaults",
+            ObjectType::ExtendedProperty       => "Extended Properties",
+            ObjectType::ExternalDataSource     => "External Data Sources",
+            ObjectType::ExternalFileFormat     => "External File Formats",
+            ObjectType::ExternalTable          => "External Tables",
+            ObjectType::FullTextCatalog        => "Storage/Full Text Catalogs",
+            ObjectType::FullTextIndex          => "Storage/Full Text Indexes",
+            ObjectType::FullTextStopList       => "Storage/Full Text Stoplists",
+            ObjectType::Index                  => "Indexes",
+            ObjectType::Login                  => "Security/Logins",
+            ObjectType::MessageType            => "Service Broker/Message Types",
+            ObjectType::PartitionFunction      => "Storage/Partition Functions",
+            ObjectType::PartitionScheme        => "Storage/Partition Schemes",
+            ObjectType::PlanGuide              => "Plan Guides",
+            ObjectType::Queue                  => "Service Broker/Queues",
+            ObjectType::Route                  => "Service Broker/Routes",
+            ObjectType::Rule                   => "Rules",
+            ObjectType::Schema                 => "Security/Schemas",
+            ObjectType::SecurityPolicy         => "Security/Security Policies",
+            ObjectType::Sequence               => "Sequences",
+            ObjectType::Service                => "Service Broker/Services",
+            ObjectType::Statistic              => "Statistics",
+            ObjectType::StoredProcedure        => "Stored Procedures",
+            ObjectType::SymmetricKey           => "Security/Symmetric Keys",
+            ObjectType::Synonym                => "Synonyms",
+            ObjectType::Table                  => "Tables",
+            ObjectType::Trigger                => "Triggers",
+            ObjectType::User                   => "Security/Users",
+            ObjectType::UserDefinedAggregate   => "Aggregates",
+            ObjectType::UserDefinedDataType
+            | ObjectType::UserDefinedTableType => "Types/User-defined Data Types",
+            ObjectType::UserDefinedFunction    => "Functions",
+            ObjectType::View                   => "Views",
+            ObjectType::XmlSchemaCollection    => "Types/XML Schema Collections",
+            _                                  => return self.folder(),
+        };
+        folder.to_string()
+    }
+}
+
+impl std::fmt::Display for ObjectType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObjectType::ApplicationRole      => write!(f, "ApplicationRole"),
+            ObjectType::Assembly             => write!(f, "Assembly"),
+            ObjectType::AsymmetricKey        => write!(f, "AsymmetricKey"),
+            ObjectType::Certificate          => write!(f, "Certificate"),
+            ObjectType::ColumnEncryptionKey  => write!(f, "ColumnEncryptionKey"),
+            ObjectType::ColumnMasterKey      => write!(f, "ColumnMasterKey"),
+            ObjectType::Constraint           => write!(f, "Constraint"),
+            ObjectType::Contract             => write!(f, "Contract"),
+            ObjectType::Data                 => write!(f, "Data"),
+            ObjectType::Database             => write!(f, "Database"),
+            ObjectType::DatabaseRole         => write!(f, "DatabaseRole"),
+            ObjectType::DdlTrigger           => write!(f, "DdlTrigger"),
+            ObjectType::Default              => write!(f, "Default"),
+            ObjectType::ExtendedProperty     => write!(f, "ExtendedProperty"),
+            ObjectType::ExternalDataSource   => write!(f, "ExternalDataSource"),
+            ObjectType::ExternalFileFormat   => write!(f, "ExternalFileFormat"),
+            ObjectType::ExternalTable        => write!(f, "ExternalTable"),
+            ObjectType::FullTextCatalog      => write!(f, "FullTextCatalog"),
+            ObjectType::FullTextIndex        => write!(f, "FullTextIndex"),
+            ObjectType::FullTextStopList     => write!(f, "FullTextStopList"),
+            ObjectType::Index                => write!(f, "Index"),
+            ObjectType::Login                => write!(f, "Login"),
+            ObjectType::MessageType          => write!(f, "MessageType"),
+            ObjectType::Package              => write!(f, "Package"),
+            ObjectType::PackageBody          => write!(f, "PackageBody"),
+            ObjectType::PartitionFunction    => write!(f, "PartitionFunction"),
+            ObjectType::PartitionScheme      => write!(f, "PartitionScheme"),
+            ObjectType::Permission           => write!(f, "Permission"),
+            ObjectType::PlanGuide            => write!(f, "PlanGuide"),
+            ObjectType::Queue                => write!(f, "Queue"),
+            ObjectType::Route                => write!(f, "Route"),
+            ObjectType::Rule                 => write!(f, "Rule"),
+            ObjectType::Schema               => write!(f, "Schema"),
+            ObjectType::Section              => write!(f, "Section"),
+            ObjectType::SecurityPolicy       => write!(f, "SecurityPolicy"),
+            ObjectType::Sequence             => write!(f, "Sequence"),
+            ObjectType::Service              => write!(f, "Service"),
+            ObjectType::Statistic            => write!(f, "Statistic"),
+            ObjectType::StoredProcedure      => write!(f, "StoredProcedure"),
+            ObjectType::SymmetricKey         => write!(f, "SymmetricKey"),
+            ObjectType::Synonym              => write!(f, "Synonym"),
+            ObjectType::Table                => write!(f, "Table"),
+            ObjectType::Trigger              => write!(f, "Trigger"),
+            ObjectType::User                 => write!(f, "User"),
+            ObjectType::UserDefinedAggregate => write!(f, "UserDefinedAggregate"),
+            ObjectType::UserDefinedDataType  => write!(f, "UserDefinedDataType"),
+            ObjectType::UserDefinedFunction  => write!(f, "UserDefinedFunction"),
+            ObjectType::UserDefinedTableType => write!(f, "UserDefinedTableType"),
+            ObjectType::View                 => write!(f, "View"),
+            ObjectType::XmlSchemaCollection  => write!(f, "XmlSchemaCollection"),
+            ObjectType::Custom(custom)       => write!(f, "{}", custom.header),
+            ObjectType::Other(name)          => write!(f, "{}", name),
+        }
+    }
+}
+
+/// object types (as written to records, including the parts of tables) in
+/// an order they can be created in on an empty database
+const DEPLOY_ORDER: &[&str] = &[
+    "Database",
+    "Login",
+    "User",
+    "DatabaseRole",
+    "ApplicationRole",
+    "Schema",
+    "Assembly",
+    "XmlSchemaCollection",
+    "UserDefinedDataType",
+    "UserDefinedTableType",
+    "UserDefinedAggregate",
+    "PartitionFunction",
+    "PartitionScheme",
+    "FullTextCatalog",
+    "FullTextStopList",
+    "Certificate",
+    "AsymmetricKey",
+    "SymmetricKey",
+    "ColumnMasterKey",
+    "ColumnEncryptionKey",
+    "ExternalDataSource",
+    "ExternalFileFormat",
+    "Sequence",
+    "Default",
+    "Rule",
+    "Table",
+    "ExternalTable",
+    "Data",
+    "Constraint",
+    "Constraints",
+    "Index",
+    "Indexes",
+    "Statistic",
+    "FullTextIndex",
+    "Package",
+    "UserDefinedFunction",
+    "View",
+    "Synonym",
+    "StoredProcedure",
+    "PackageBody",
+    "Trigger",
+    "DdlTrigger",
+    "SecurityPolicy",
+    "MessageType",
+    "Contract",
+    "Queue",
+    "Service",
+    "Route",
+    "PlanGuide",
+    "ExtendedProperty",
+    "Permission",
+];
+
+/// where objects of `object_type` go in a deployment; types this tool
+/// doesn't know come last
+pub fn deploy_rank(object_type: &str) -> usize {
+    DEPLOY_ORDER.iter()
+        .position(|t| *t == object_type)
+        .unwrap_or(DEPLOY_ORDER.len())
+}
+
+/// how the output is organized into folders
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Layout {
+    // a folder named after each object type, e.g. StoredProcedure/
+    Types,
+    // the folders of Redgate SQL Source Control, e.g. Stored Procedures/
+    Redgate,
+}
+
+/// where objects that belong to a table (e.g. statistics) are written
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Placement {
+    // in their type's own folder
+    Folder,
+    // in the table's file, after the table
+    WithTable,
+}
+
+/// where extended properties are written
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum PropertyPlacement {
+    // in the file of the object they're on, if they follow it, otherwise in
+    // the ExtendedProperties folder
+    WithObject,
+    // always in the ExtendedProperties folder
+    Folder,
+}
+
+/// where permission statements are written
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum PermissionPlacement {
+    // in the file of the object they follow
+    WithObject,
+    // in the Security/Permissions folder, one file per securable
+    Separate,
+}
+
+/// what to do with an object that has the same file as one before it, e.g.
+/// in scripts that were concatenated
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum OnDuplicate {
+    // write only the first one
+    KeepFirst,
+    // write only the last one
+    KeepLast,
+    // write the later ones to <name>.dup<N>.sql
+    Both,
+    // stop with an error
+    Error,
+}
+
+/// the table that `content`, the script of an object that belongs to a table,
+/// is ON (or alters), as (schema, name); the bracketed groups of `pattern`
+/// have ] doubled in them
+pub fn parent_table(pattern: &Regex, content: &str) -> Option<(String, String)> {
+    let caps = pattern.captures(content)?;
+    let schema = caps.get(1).or(caps.get(2))?.as_str().replace("]]", "]");
+    let name = caps.get(3).or(caps.get(4))?.as_str().replace("]]", "]");
+    Some((schema, name))
+}
+
+pub struct DatabaseObject {
+    pub object_type: ObjectType,
+    /// the database, if the header names it ([database].[schema].[name])
+    pub database:    Option<String>,
+    pub schema:      String,
+    pub name:        String,
+    /// the number of a numbered procedure (`[name];N`)
+    pub number:      Option<u32>,
+}
+
+/// recognizes object headers, of the built-in types as well as those declared
+/// in the configuration file
+pub struct Headers {
+    dialect:    Dialect,
+    pattern:    Regex,
+    custom:     Vec<CustomType>,
+    layout:     Layout,
+    /// the capture group of `pattern` that names objects, for
+    /// Dialect::Pattern
+    name_group: usize,
+    /// whether `pattern` is --header-pattern's, with named groups
+    named:      bool,
+}
+
+/// `name`, from between backquotes (as mysqldump quotes every name), with
+/// its doubled backquotes undone
+fn unquote_backquoted(name: &str) -> String {
+    name.replace("``", "`")
+}
+
+/// `words`, e.g. `FOREIGN DATA WRAPPER` or `Full-text Catalog`, as a type
+/// name: `ForeignDataWrapper`, `FullTextCatalog`
+fn pascal_case(words: &str) -> String {
+    words.split([' ', '-'])
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map_or(String::new(), |first| first.to_uppercase().collect())
+                + &chars.as_str().to_lowercase()
+        })
+        .collect()
+}
+
+/// `name` without the brackets (or double quotes) around it, if any
+fn unquote_name(name: &str) -> String {
+    let name = name.trim();
+    match name.strip_prefix('[').and_then(|n| n.strip_suffix(']')) {
+        Some(inner) => inner.replace("]]", "]"),
+        None        => unquote_pg(name),
+    }
+}
+
+/// `[schema].[name]`, or `[name]` for objects that aren't in a schema, with
+/// the closing brackets in the names doubled
+pub fn quoted_name(schema: &str, name: &str) -> String {
+    let quoted = format!("[{}]", name.replace(']', "]]"));
+    if schema.is_empty() {
+        quoted
+    } else {
+        format!("[{}].{}", schema.replace(']', "]]"), quoted)
+    }
+}
+
+/// `name` without the double quotes pg_dump puts around names that need them
+fn unquote_pg(name: &str) -> String {
+    match name.strip_prefix('"').and_then(|n| n.strip_suffix('"')) {
+        Some(inner) if !inner.contains("\".\"") => inner.replace("\"\"", "\""),
+        _                                       => name.to_string(),
+    }
+}
+
+/// the types that only Azure SQL Database and Synapse have, as declared
+/// types: (header, folder, schema_qualified)
+const AZURE_TYPES: &[(&str, &str, bool)] = &[
+    ("DatabaseScopedCredential",   "Security/DatabaseScopedCredential", false),
+    ("DatabaseAuditSpecification", "Security/DatabaseAuditSpecification", false),
+    ("ExternalLanguage",           "ExternalLanguage", false),
+    ("ExternalLibrary",            "ExternalLibrary", false),
+    ("WorkloadGroup",              "WorkloadManagement/WorkloadGroup", false),
+    ("WorkloadClassifier",         "WorkloadManagement/WorkloadClassifier", false),
+];
+
+impl Headers {
+    pub fn new(custom: Vec<CustomType>, layout: Layout, dialect: Dialect) -> Self {
+        let mut custom = custom;
+        if dialect == Dialect::Azure {
+            // the configuration file's declarations come first, and so win
+            custom.extend(AZURE_TYPES.iter().map(|&(header, folder, schema_qualified)| CustomType {
+                header:           header.to_string(),
+                folder:           Some(folder.to_string()),
+                schema_qualified,
+            }));
+        }
+        let pattern = match dialect {
+            // [schema].[name], or just [name] for objects that aren't in a
+            // schema (e.g. partition functions), or [database].[schema].[name];
+            // numbered procedures are followed by ;N (--split-on brings its
+            // own pattern, see `splitting_on`)
+            Dialect::Ssms
+            | Dialect::Azure
+            | Dialect::Pattern => r"^/\*+\s*Object:\s+(\w+)\s+\[((?:[^\]]|\]\])+)\](?:\.\[((?:[^\]]|\]\])+)\])?(?:\.\[((?:[^\]]|\]\])+)\])?(?:;(\d+))?",
+            // Stored Procedure dbo.usp_Orders, or Table [dbo].[Orders]: the
+            // known types, since they're spelled with spaces
+            Dialect::Ssms2000 => r"^/\*+\s*Object:\s+(?P<type>Stored Procedure|Extended Procedure|User Defined Function|User Defined Data Type|Full-text Catalog|Table|View|Trigger|Index|Rule|Default|Role|Login|User|Database)\s+(?:(?P<schema>\[(?:[^\]]|\]\])+\]|[^\s.\[]+)\.)?(?P<name>\[(?:[^\]]|\]\])+\]|[^\s\[*]+)",
+            // -- [Data for ]Name: <name>; Type: <type>; Schema: <schema or ->; ...
+            Dialect::PgDump => r"^--\s+(Data for )?Name:\s+(.*?);\s+Type:\s+(.*?);\s+Schema:\s+(.*?);",
+            // CREATE [TEMP] TABLE|INDEX|VIEW|TRIGGER [IF NOT EXISTS] name, or
+            // INSERT INTO name for a table's data; names are quoted any of
+            // the ways SQLite accepts, or not at all
+            Dialect::Sqlite => r#"(?i)^(?:CREATE\s+(?:TEMP\s+|TEMPORARY\s+)?(TABLE|VIRTUAL\s+TABLE|(?:UNIQUE\s+)?INDEX|VIEW|TRIGGER)|(INSERT\s+INTO|DELETE\s+FROM))\s+(?:IF\s+NOT\s+EXISTS\s+)?(?:"((?:[^"]|"")+)"|\[([^\]]+)\]|`((?:[^`]|``)+)`|'((?:[^']|'')+)'|(\w+))"#,
+            // --  DDL for <type> <name>, --  [Ref ]Constraints for Table <name>,
+            // or REM INSERTING into [<schema>.]<name>
+            Dialect::Oracle => r"^(?:--\s+(?:DDL for (.+?)|(Ref )?Constraints for Table)|REM INSERTING into)\s+(?:\S+\.)?(\S+?)\s*$",
+            // -- Table structure for table `name` (and the like), or the
+            // first line of a routine or trigger
+            Dialect::Mysql  => r"^(?:--\s+(Table structure for table|Dumping data for table|Final view structure for view)|/\*!\d+\s+DROP\s+(PROCEDURE|FUNCTION|EVENT)\s+IF\s+EXISTS|/\*!\d+\s+CREATE\s*\*/.*?\b(TRIGGER))\s+`((?:[^`]|``)+)`",
+        };
+        Headers {
+            dialect,
+            pattern:    Regex::new(pattern)
+                .expect("error compiling DatabaseObject regular expression"),
+            custom,
+            layout,
+            name_group: 0,
+            named:      dialect == Dialect::Ssms2000,
+        }
+    }
+
+    /// these headers, recognized by `pattern` (--header-pattern) instead of
+    /// the built-in one: its `type`, `schema` and `name` groups are the
+    /// object's; `schema` is optional
+    pub fn with_named_pattern(self, pattern: Regex) -> Self {
+        Headers {
+            pattern,
+            named: true,
+            ..self
+        }
+    }
+
+    /// recognizes the lines that match `pattern` (--split-on), naming them
+    /// after capture group `name_group`
+    pub fn splitting_on(pattern: Regex, name_group: usize, layout: Layout) -> Self {
+        Headers {
+            dialect: Dialect::Pattern,
+            pattern,
+            custom: Vec::new(),
+            layout,
+            name_group,
+            named:  false,
+        }
+    }
+
+    /// the object `s`, an object header, is for, if it can be parsed
+    pub fn parse(&self, s: &str) -> Option<DatabaseObject> {
+        if self.named {
+            return self.parse_named(s);
+        }
+        match self.dialect {
+            Dialect::Ssms
+            | Dialect::Ssms2000
+            | Dialect::Azure => self.parse_ssms(s),
+            Dialect::PgDump  => self.parse_pg_dump(s),
+            Dialect::Mysql   => self.parse_mysql(s),
+            Dialect::Sqlite  => self.parse_sqlite(s),
+            Dialect::Oracle  => self.parse_oracle(s),
+            Dialect::Pattern => self.parse_pattern(s),
+        }
+    }
+
+    /// whether `s`, an object header, starts a part of the script that isn't
+    /// an object and is left out: the stand-ins mysqldump creates for views
+    /// so that other views can refer to them before they're scripted, and
+    /// the switches to a database (with its CREATE DATABASE)
+    pub fn is_skipped(&self, s: &str) -> bool {
+        match self.dialect {
+            Dialect::Mysql  => s.starts_with("-- Temporary ") || s.starts_with("-- Current Database: "),
+            // SQL Developer's banner at the top of the export
+            Dialect::Oracle => s.trim_start_matches('-').trim_start().starts_with("File created - "),
+            // the statements around the dump, e.g. BEGIN TRANSACTION
+            Dialect::Sqlite => !["CREATE ", "INSERT ", "DELETE "].iter()
+                .any(|statement| s.get(..statement.len()).is_some_and(|start| start.eq_ignore_ascii_case(statement))),
+            _               => false,
+        }
+    }
+
+    /// the section a line matching --split-on starts, named after the text
+    /// of its name group; there is none if the group didn't match anything
+    fn parse_pattern(&self, s: &str) -> Option<DatabaseObject> {
+        let caps = self.pattern.captures(s.trim_end_matches(['\r', '\n']))?;
+        let name = caps.get(self.name_group)?.as_str().trim();
+        if name.is_empty() {
+            return None;
+        }
+        Some(DatabaseObject {
+            object_type: ObjectType::Section,
+            database:    None,
+            schema:      String::new(),
+            name:        name.to_string(),
+            number:      None,
+        })
+    }
+
+    /// the object of one of the headers of Oracle SQL Developer's exports;
+    /// a table's constraints are scripted together, with its foreign keys
+    /// (which are created after every table) apart
+    fn parse_oracle(&self, s: &str) -> Option<DatabaseObject> {
+        let caps = self.pattern.captures(s)?;
+        // the DDL headers don't name the schema, so data goes without it too,
+        // next to its table
+        let name = caps[3].to_string();
+        let (object_type, name) = match caps.get(1).map(|keyword| keyword.as_str()) {
+            None if s.starts_with("REM")   => (ObjectType::Data, name),
+            None if caps.get(2).is_some()  => (ObjectType::Constraint, format!("{}.foreign_keys", name)),
+            None                           => (ObjectType::Constraint, name),
+            Some("Table")                  => (ObjectType::Table, name),
+            Some("Index")                  => (ObjectType::Index, name),
+            Some("View")
+            | Some("Materialized View")    => (ObjectType::View, name),
+            Some("Sequence")               => (ObjectType::Sequence, name),
+            Some("Synonym")                => (ObjectType::Synonym, name),
+            Some("Procedure")              => (ObjectType::StoredProcedure, name),
+            Some("Function")               => (ObjectType::UserDefinedFunction, name),
+            Some("Trigger")                => (ObjectType::Trigger, name),
+            Some("Type")                   => (ObjectType::UserDefinedDataType, name),
+            Some("Package")                => (ObjectType::Package, name),
+            Some("Package Body")           => (ObjectType::PackageBody, name),
+            // e.g. DB Link as DbLink
+            Some(other)                    => (ObjectType::Other(pascal_case(other)), name),
+        };
+        Some(DatabaseObject {
+            object_type,
+            database: None,
+            schema:   String::new(),
+            name,
+            number:   None,
+        })
+    }
+
+    /// the object a statement in SQLite's dumps starts; a table's data is
+    /// its INSERTs (and for sqlite_sequence, the DELETE before them)
+    fn parse_sqlite(&self, s: &str) -> Option<DatabaseObject> {
+        let caps = self.pattern.captures(s)?;
+        let keyword = match caps.get(1) {
+            Some(keyword) => keyword.as_str().to_uppercase(),
+            None          => String::from("INSERT"),
+        };
+        let object_type = match keyword.split_whitespace().last() {
+            Some("TABLE")   => ObjectType::Table,
+            Some("INDEX")   => ObjectType::Index,
+            Some("VIEW")    => ObjectType::View,
+            Some("TRIGGER") => ObjectType::Trigger,
+            _               => ObjectType::Data,
+        };
+        let name = if let Some(quoted) = caps.get(3) {
+            quoted.as_str().replace("\"\"", "\"")
+        } else if let Some(quoted) = caps.get(4) {
+            quoted.as_str().to_string()
+        } else if let Some(quoted) = caps.get(5) {
+            unquote_backquoted(quoted.as_str())
+        } else if let Some(quoted) = caps.get(6) {
+            quoted.as_str().replace("''", "'")
+        } else {
+            caps[7].to_string()
+        };
+        Some(DatabaseObject {
+            object_type,
+            database: None,
+            schema:   String::new(),
+            name,
+            number:   None,
+        })
+    }
+
+    /// the object of one of mysqldump's headers; databases have no schemas
+    fn parse_mysql(&self, s: &str) -> Option<DatabaseObject> {
+        let caps = self.pattern.captures(s)?;
+        let keyword = caps.get(1).or_else(|| caps.get(2)).or_else(|| caps.get(3))?.as_str();
+        let object_type = match keyword {
+            "Table structure for table"     => ObjectType::Table,
+            "Dumping data for table"        => ObjectType::Data,
+            "Final view structure for view" => ObjectType::View,
+            "PROCEDURE"                     => ObjectType::StoredProcedure,
+            "FUNCTION"                      => ObjectType::UserDefinedFunction,
+            "TRIGGER"                       => ObjectType::Trigger,
+            _                               => ObjectType::Other(String::from("Event")),
+        };
+        Some(DatabaseObject {
+            object_type,
+            database: None,
+            schema:   String::new(),
+            name:     unquote_backquoted(&caps[4]),
+            number:   None,
+        })
+    }
+
+    /// the object of one of pg_dump's headers; objects that pg_dump scripts
+    /// apart from the one they belong to (e.g. comments, or a table's
+    /// constraints) are named after both
+    fn parse_pg_dump(&self, s: &str) -> Option<DatabaseObject> {
+        let caps = self.pattern.captures(s)?;
+        let data = caps.get(1).is_some();
+        let name = caps[2].trim();
+        let keyword = caps[3].trim();
+        let schema = match caps[4].trim() {
+            "-"    => String::new(),
+            schema => unquote_pg(schema),
+        };
+        // e.g. "TABLE orders" for the comment on a table, or
+        // "orders orders_pkey" for a constraint of a table
+        let without_keywords = name.trim_start_matches(|c: char| c.is_ascii_uppercase() || c == ' ');
+        let qualified = |name: &str| name.split(' ').map(unquote_pg).collect::<Vec<_>>().join(".");
+        let (object_type, name) = match keyword {
+            _ if data                    => (ObjectType::Data, unquote_pg(name)),
+            "SCHEMA"                     => (ObjectType::Schema, unquote_pg(name)),
+            "TABLE"                      => (ObjectType::Table, unquote_pg(name)),
+            "FOREIGN TABLE"              => (ObjectType::ExternalTable, unquote_pg(name)),
+            "VIEW"
+            | "MATERIALIZED VIEW"        => (ObjectType::View, unquote_pg(name)),
+            "FUNCTION"                   => (ObjectType::UserDefinedFunction, name.to_string()),
+            "PROCEDURE"                  => (ObjectType::StoredProcedure, name.to_string()),
+            "AGGREGATE"                  => (ObjectType::UserDefinedAggregate, name.to_string()),
+            "SEQUENCE"
+            | "SEQUENCE OWNED BY"        => (ObjectType::Sequence, unquote_pg(name)),
+            "INDEX"                      => (ObjectType::Index, unquote_pg(name)),
+            "TYPE"
+            | "DOMAIN"                   => (ObjectType::UserDefinedDataType, unquote_pg(name)),
+            "DATABASE"                   => (ObjectType::Database, unquote_pg(name)),
+            "EVENT TRIGGER"              => (ObjectType::DdlTrigger, unquote_pg(name)),
+            "TRIGGER"
+            | "RULE"
+            | "POLICY"                   => {
+                let object_type = match keyword {
+                    "TRIGGER" => ObjectType::Trigger,
+                    "RULE"    => ObjectType::Rule,
+                    _         => ObjectType::SecurityPolicy,
+                };
+                // "<table> <name>"; the name is unique enough for a file
+                let name = name.rsplit_once(' ').map_or(name, |(_, name)| name);
+                (object_type, unquote_pg(name))
+            },
+            "CONSTRAINT"
+            | "FK CONSTRAINT"
+            | "CHECK CONSTRAINT"
+            | "DEFAULT"                  => (ObjectType::Constraint, qualified(name)),
+            "ACL"                        => (ObjectType::Permission, qualified(without_keywords)),
+            "COMMENT"                    => (ObjectType::ExtendedProperty, qualified(without_keywords)),
+            // e.g. EXTENSION, or FOREIGN DATA WRAPPER as ForeignDataWrapper
+            other => (ObjectType::Other(pascal_case(other)), unquote_pg(name)),
+        };
+        Some(DatabaseObject {
+            object_type,
+            database: None,
+            schema,
+            name,
+            number: None,
+        })
+    }
+
+    /// the object of an SSMS header
+    fn parse_ssms(&self, s: &str) -> Option<DatabaseObject> {
+        let caps = self.pattern.captures(s)?;
+        let keyword = caps.get(1).expect("Error retrieving capture group").as_str();
+        // the names are inside brackets, in which ] is doubled
+        let first = caps.get(2).unwrap().as_str().replace("]]", "]");
+        let (database, schema, name) = match (caps.get(3), caps.get(4)) {
+            (Some(schema), Some(name)) => (Some(first), schema.as_str().replace("]]", "]"), name.as_str().replace("]]", "]")),
+            (Some(name), None)         => (None, first, name.as_str().replace("]]", "]")),
+            _                          => (None, String::new(), first),
+        };
+        let number = caps.get(5).and_then(|number| number.as_str().parse().ok());
+        self.object(keyword, database, schema, name, number)
+    }
+
+    /// the object of a header matched by --header-pattern; types may be
+    /// written with spaces, e.g. `Stored Procedure`, and names may be quoted
+    fn parse_named(&self, s: &str) -> Option<DatabaseObject> {
+        let caps = self.pattern.captures(s.trim_end_matches(['\r', '\n']))?;
+        let keyword = caps.name("type")?.as_str().trim();
+        let keyword = if keyword.contains(char::is_whitespace) {
+            pascal_case(keyword)
+        } else {
+            keyword.to_string()
+        };
+        let schema = caps.name("schema").map_or_else(String::new, |schema| unquote_name(schema.as_str()));
+        let name = unquote_name(caps.name("name")?.as_str());
+        if name.is_empty() {
+            return None;
+        }
+        self.object(&keyword, None, schema, name, None)
+    }
+
+    /// the object of type `keyword` (as SSMS names types, or as declared in
+    /// the configuration file) with the given name
+    fn object(
+        &self,
+        keyword:  &str,
+        database: Option<String>,
+        schema:   String,
+        name:     String,
+        number:   Option<u32>,
+    ) -> Option<DatabaseObject> {
+        // declared types take precedence, so that built-in ones can be
+        // written elsewhere too
+        if let Some(custom) = self.custom.iter().find(|custom| custom.header == keyword) {
+            if custom.schema_qualified == schema.is_empty() {
+                return None;
+            }
+            return Some(DatabaseObject {
+                object_type: ObjectType::Custom(custom.clone()),
+                database,
+                schema,
+                name,
+                number,
+            });
+        }
+
+        let object_type = match keyword {
+            "ApplicationRole"      => ObjectType::ApplicationRole,
+            "Assembly"
+            | "SqlAssembly"        => ObjectType::Assembly,
+            "AsymmetricKey"        => ObjectType::AsymmetricKey,
+            "Certificate"          => ObjectType::Certificate,
+            "ColumnEncryptionKey"  => ObjectType::ColumnEncryptionKey,
+            "ColumnMasterKey"      => ObjectType::ColumnMasterKey,
+            "Contract"
+            | "ServiceContract"    => ObjectType::Contract,
+            "Database"             => ObjectType::Database,
+            "DatabaseRole"
+            | "Role"               => ObjectType::DatabaseRole,
+            "DdlTrigger"           => ObjectType::DdlTrigger,
+            "Default"              => ObjectType::Default,
+            "ExternalDataSource"   => ObjectType::ExternalDataSource,
+            "ExternalFileFormat"   => ObjectType::ExternalFileFormat,
+            "ExternalTable"        => ObjectType::ExternalTable,
+            "FullTextCatalog"      => ObjectType::FullTextCatalog,
+            "FullTextIndex"        => ObjectType::FullTextIndex,
+            "FullTextStopList"     => ObjectType::FullTextStopList,
+            "Index"                => ObjectType::Index,
+            "Login"                => ObjectType::Login,
+            "MessageType"          => ObjectType::MessageType,
+            "PartitionFunction"    => ObjectType::PartitionFunction,
+            "PartitionScheme"      => ObjectType::PartitionScheme,
+            "PlanGuide"            => ObjectType::PlanGuide,
+            "Queue"
+            | "ServiceQueue"       => ObjectType::Queue,
+            "Route"
+            | "ServiceRoute"       => ObjectType::Route,
+            "Rule"                 => ObjectType::Rule,
+            "Schema"               => ObjectType::Schema,
+            "SecurityPolicy"       => ObjectType::SecurityPolicy,
+            "Sequence"             => ObjectType::Sequence,
+            "Service"
+            | "BrokerService"      => ObjectType::Service,
+            "Statistic"            => ObjectType::Statistic,
+            "StoredProcedure"      => ObjectType::StoredProcedure,
+            "SymmetricKey"         => ObjectType::SymmetricKey,
+            "Synonym"              => ObjectType::Synonym,
+            "Table"                => ObjectType::Table,
+            "Trigger"              => ObjectType::Trigger,
+            "User"                 => ObjectType::User,
+            "UserDefinedAggregate" => ObjectType::UserDefinedAggregate,
+            "UserDefinedDataType"  => ObjectType::UserDefinedDataType,
+            "UserDefinedFunction"  => ObjectType::UserDefinedFunction,
+            "UserDefinedTableType" => ObjectType::UserDefinedTableType,
+            "View"                 => ObjectType::View,
+            "XmlSchemaCollection"  => ObjectType::XmlSchemaCollection,
+            // written to Other/<type>, so that nothing is lost
+            other                  => ObjectType::Other(other.to_string()),
+        };
+        Some(DatabaseObject {
+            object_type,
+            database,
+            schema,
+            name,
+            number,
+        })
+    }
+
+    /// the folders (relative to `out_dir`) that objects are written to: those
+    /// of the built-in and declared types, plus those of other types already
+    /// in `out_dir`
+    pub fn type_folders(&self, out_dir: &str) -> Vec<String> {
+        let mut folders: Vec<String> = Vec::new();
+        for object_type in ObjectType::ALL {
+            let folder = object_type.folder_in(self.layout);
+            if !folders.contains(&folder) {
+                folders.push(folder);
+            }
+        }
+        for custom in self.custom.iter() {
+            let folder = ObjectType::Custom(custom.clone()).folder();
+            if !folders.contains(&folder) {
+                folders.push(folder);
+            }
+        }
+        let mut others: Vec<String> = fs::read_dir(Path::new(out_dir).join("Other"))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| format!("Other/{}", entry.file_name().to_string_lossy()))
+            .collect();
+        others.sort();
+        folders.append(&mut others);
+        folders
+    }
+}
+
+/// get the database name from a `USE [db]` (or ``USE `db` ``) statement, or from psql's
+/// `\connect db` (or `\connect -reuse-previous=on "dbname='db'"`)
+pub fn parse_use_statement(s: &str) -> Option<String> {
+    use_statement_name(s).map(|(database, _, _)| database)
+}
+
+/// `s`, a USE (or `\connect`) statement, selecting `database` instead of the
+/// database it names, quoted the same way
+pub fn rename_use_statement(s: &str, database: &str) -> Option<String> {
+    let (_, span, quote) = use_statement_name(s)?;
+    let quoted = match quote {
+        Some(']')  => database.replace(']', "]]"),
+        Some('\'') => database.replace('\'', "''"),
+        Some('"')  => database.replace('"', "\"\""),
+        Some('`')  => database.replace('`', "``"),
+        _          => database.to_string(),
+    };
+    Some(format!("{}{}{}", &s[..span.start], quoted, &s[span.end..]))
+}
+
+/// the database named by a USE statement, where its name is in `s` (inside
+/// the quotes, if any) and the quote that ends it
+fn use_statement_name(s: &str) -> Option<(String, Range<usize>, Option<char>)> {
+    static PATTERN: Lazy<Regex> = Lazy::new(|| Regex::new(r#"(?i)^\s*(?:USE|\\connect(?:\s+-reuse-previous=\w+)?)\s+(?:\[((?:[^\]]|\]\])+)\]|"dbname='((?:[^']|'')+)'"|"((?:[^"]|"")+)"|`((?:[^`]|``)+)`|([^\s;]+))"#)
+        .expect("error compiling USE statement regular expression"));
+    if !starts_with_keyword(s, "USE") && !starts_with_keyword(s, "\\connect") {
+        return None;
+    }
+    let caps = PATTERN.captures(s)?;
+    if let Some(quoted) = caps.get(1) {
+        Some((quoted.as_str().replace("]]", "]"), quoted.range(), Some(']')))
+    } else if let Some(dbname) = caps.get(2) {
+        Some((dbname.as_str().replace("''", "'"), dbname.range(), Some('\'')))
+    } else if let Some(quoted) = caps.get(3) {
+        Some((quoted.as_str().replace("\"\"", "\""), quoted.range(), Some('"')))
+    } else if let Some(quoted) = caps.get(4) {
+        Some((unquote_backquoted(quoted.as_str()), quoted.range(), Some('`')))
+    } else {
+        caps.get(5).map(|name| (name.as_str().to_string(), name.range(), None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pascal_case_joins_words() {
+        assert_eq!(pascal_case("FOREIGN DATA WRAPPER"), "ForeignDataWrapper");
+        assert_eq!(pascal_case("Full-text Catalog"), "FullTextCatalog");
+        assert_eq!(pascal_case("  search  path "), "SearchPath");
+    }
+
+    #[test]
+    fn pascal_case_handles_non_ascii_first_characters() {
+        assert_eq!(pascal_case("élément ÜBERSICHT"), "ÉlémentÜbersicht");
+        assert_eq!(pascal_case("ßtraße"), "SStraße");
+    }
+
+    // the folder, schema and name of the object a header is parsed to
+    fn parsed(dialect: Dialect, header: &str) -> (String, String, String) {
+        let headers = Headers::new(Vec::new(), Layout::Types, dialect);
+        let object = headers.parse(header).expect("header should parse");
+        (object.object_type.folder_in(Layout::Types), object.schema, object.name)
+    }
+
+    fn expected(folder: &str, schema: &str, name: &str) -> (String, String, String) {
+        (folder.to_string(), schema.to_string(), name.to_string())
+    }
+
+    #[test]
+    fn ssms2000_headers_parse_to_their_objects() {
+        let cases = [
+            ("/****** Object:  Stored Procedure dbo.usp_One    Script Date: 16.10.2026 12:00:00 ******/",
+             expected("StoredProcedure", "dbo", "usp_One")),
+            ("/****** Object:  Table [dbo].[Orders]    Script Date: 16.10.2026 12:00:00 ******/",
+             expected("Table", "dbo", "Orders")),
+            ("/****** Object:  User Defined Function dbo.fn_X ******/",
+             expected("UserDefinedFunction", "dbo", "fn_X")),
+            ("/*** Object:  User guest ***/",
+             expected("User", "", "guest")),
+            ("/****** Object:  Role db_app    Script Date: 16.10.2026 12:00:00 ******/",
+             expected("DatabaseRole", "", "db_app")),
+            ("/****** Object:  Full-text Catalog ftc    Script Date: 16.10.2026 12:00:00 ******/",
+             expected("FullTextCatalog", "", "ftc")),
+        ];
+        for (header, object) in cases {
+            assert_eq!(parsed(Dialect::Ssms2000, header), object, "{}", header);
+        }
+    }
+
+    #[test]
+    fn azure_headers_parse_to_their_objects() {
+        let cases = [
+            ("/****** Object:  DatabaseScopedCredential [blob_cred]    Script Date: 10/16/2026 ******/",
+             expected("Security/DatabaseScopedCredential", "", "blob_cred")),
+            ("/****** Object:  WorkloadGroup [wg_etl]    Script Date: 10/16/2026 ******/",
+             expected("WorkloadManagement/WorkloadGroup", "", "wg_etl")),
+            ("/****** Object:  Table [dbo].[T]    Script Date: 10/16/2026 ******/",
+             expected("Table", "dbo", "T")),
+        ];
+        for (header, object) in cases {
+            assert_eq!(parsed(Dialect::Azure, header), object, "{}", header);
+        }
+    }
+
+    #[test]
+    fn doubled_brackets_in_ssms_headers_are_unescaped() {
+        let (folder, schema, name) = parsed(Dialect::Ssms,
+            "/****** Object:  StoredProcedure [dbo].[usp_[Orders]]]    Script Date: 10/16/2026 ******/");
+        assert_eq!((folder.as_str(), schema.as_str(), name.as_str()), ("StoredProcedure", "dbo", "usp_[Orders]"));
+        let (_, schema, name) = parsed(Dialect::Ssms, "/****** Object:  Table [a]]b].[c]]]]]    Script Date: 10/16/2026 ******/");
+        assert_eq!((schema.as_str(), name.as_str()), ("a]b", "c]]"));
+    }
+
+    #[test]
+    fn quoted_names_double_closing_brackets() {
+        assert_eq!(quoted_name("dbo", "usp_[Orders]"), "[dbo].[usp_[Orders]]]");
+        assert_eq!(quoted_name("a]b", "c"), "[a]]b].[c]");
+        assert_eq!(quoted_name("", "role]"), "[role]]]");
+    }
+}
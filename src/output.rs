@@ -0,0 +1,509 @@
+/*
+ * output destinations for split objects: either a directory tree on disk or
+ * entries inside a zip file
+ *
+ * unless disabled, output is first written to a temporary place ("staging"):
+ * a hidden directory inside the output directory, or a hidden file next to
+ * the zip file; it's only moved into place once splitting has finished. a zip
+ * file is renamed into place, so it's either the old one or the new one; the
+ * files of a directory are moved one by one, so a run that fails while
+ * splitting leaves the directory alone, but one that is interrupted while the
+ * files are moved can leave some of them old and some new (never one file
+ * half-written)
+ */
+use crate::error::{ IoContext, SplitError };
+use crate::pipeline::Deflater;
+use clap::ValueEnum;
+use std::fs::{ self, File, create_dir_all };
+use std::io::{ self, BufWriter, Write };
+use std::path::{ Path, PathBuf };
+use zip::ZipWriter;
+
+/// the directory inside an output directory that its files are staged in
+pub const STAGING_DIR: &str = ".sql-splitter-tmp";
+
+/// a temporary file or directory that is removed unless it gets committed
+pub struct Staging {
+    path:   PathBuf,
+    target: PathBuf,
+    /// folders to remove from the target before the staged output is moved
+    /// into it
+    clean:  Vec<PathBuf>,
+}
+
+impl Staging {
+    /// e.g. `out/` is staged in `out/.sql-splitter-tmp/`, so nothing but the
+    /// output directory itself needs to be writable
+    fn dir(target: &Path) -> Result<Self, SplitError> {
+        let target = absolute(target)?;
+        let path = target.join(STAGING_DIR);
+        remove_leftovers(&path)?;
+        create_dir_all(&path).create_context(&path.display().to_string())?;
+        Ok(Staging { path, target, clean: Vec::new() })
+    }
+
+    /// e.g. `out.zip` is staged in `.out.zip.sql-splitter-tmp` next to it
+    fn file(target: &Path) -> Result<Self, SplitError> {
+        let target = absolute(target)?;
+        let name = target.file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let parent = target.parent().unwrap_or(Path::new("/"));
+        let path = parent.join(format!(".{}{}", name, STAGING_DIR));
+        remove_leftovers(&path)?;
+        create_dir_all(parent).create_context(&parent.display().to_string())?;
+        Ok(Staging { path, target, clean: Vec::new() })
+    }
+
+
+    /// move the staged file or directory into place; directories are merged
+    /// into the target (which they're in) file by file
+    fn commit(self) -> Result<(), SplitError> {
+        let target = self.target.display().to_string();
+        for folder in self.clean.iter() {
+            remove_folder(folder)?;
+        }
+        let result = if self.path.is_dir() {
+            merge_dir(&self.path, &self.target)
+                .and_then(|_| fs::remove_dir_all(&self.path))
+        } else {
+            fs::rename(&self.path, &self.target)
+        };
+        result.write_context(&target)
+    }
+}
+
+/// `target` relative to the current directory, if it isn't absolute
+fn absolute(target: &Path) -> Result<PathBuf, SplitError> {
+    let absolute = if target.is_absolute() {
+        target.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .create_context(&target.display().to_string())?
+            .join(target)
+    };
+    // normalize away trailing "." components so the target has a name
+    let absolute: PathBuf = absolute.components().collect();
+    Ok(if absolute.ends_with(".") {
+        absolute.parent().map(Path::to_path_buf).unwrap_or(absolute)
+    } else {
+        absolute
+    })
+}
+
+/// remove what's left at `path` of an earlier, interrupted run
+fn remove_leftovers(path: &Path) -> Result<(), SplitError> {
+    let display = path.display().to_string();
+    if path.is_dir() {
+        fs::remove_dir_all(path).create_context(&display)?;
+    } else if path.exists() {
+        fs::remove_file(path).create_context(&display)?;
+    }
+    Ok(())
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        // nothing to do if it was committed (i.e. moved away)
+        if self.path.is_dir() {
+            let _ = fs::remove_dir_all(&self.path);
+        } else if self.path.exists() {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// a directory in the system's temporary directory, removed when dropped
+pub struct Scratch(pub PathBuf);
+
+impl Scratch {
+    /// `sql-splitter-<purpose>-<pid>`, empty
+    pub fn new(purpose: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("sql-splitter-{}-{}", purpose, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        Scratch(path)
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn remove_folder(folder: &Path) -> Result<(), SplitError> {
+    if folder.is_dir() {
+        fs::remove_dir_all(folder).write_context(&folder.display().to_string())?;
+    }
+    Ok(())
+}
+
+/// whether the file at `path` already holds exactly `contents`
+pub fn has_contents(path: &Path, contents: &[u8]) -> bool {
+    match fs::metadata(path) {
+        Ok(m) if m.is_file() && m.len() == contents.len() as u64 =>
+            fs::read(path).map(|existing| existing == contents).unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// whether `a` and `b` are both files with the same content
+fn same_file_contents(a: &Path, b: &Path) -> io::Result<bool> {
+    let (a_meta, b_meta) = match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a_meta), Ok(b_meta)) => (a_meta, b_meta),
+        _                        => return Ok(false),
+    };
+    if !a_meta.is_file() || !b_meta.is_file() || a_meta.len() != b_meta.len() {
+        return Ok(false);
+    }
+    Ok(has_contents(b, &fs::read(a)?))
+}
+
+/// move every file in `from` into the same place under `to`, replacing
+/// existing files; existing files with identical content are left untouched
+/// so their timestamps are preserved
+fn merge_dir(from: &Path, to: &Path) -> io::Result<()> {
+    create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        // (the files staged for) another output directory
+        if entry.file_name() == STAGING_DIR {
+            continue;
+        }
+        let dest = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() && dest.is_dir() {
+            merge_dir(&entry.path(), &dest)?;
+        } else if same_file_contents(&entry.path(), &dest)? {
+            fs::remove_file(entry.path())?;
+        } else {
+            if dest.is_dir() {
+                fs::remove_dir_all(&dest)?;
+            } else if cfg!(windows) && dest.exists() {
+                // rename doesn't replace existing files on windows
+                fs::remove_file(&dest)?;
+            }
+            fs::rename(entry.path(), &dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// what to do when a file that is about to be written already exists in the
+/// output directory
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum OnExisting {
+    Overwrite,
+    Skip,
+    Error,
+    // keep a copy of the existing file as `<file>.bak`
+    Backup,
+}
+
+/// what `Output::write_file` did
+pub enum Written {
+    Created,
+    /// an identical file already existed
+    Unchanged,
+    /// a file already existed and was left alone
+    Skipped,
+}
+
+pub enum Output {
+    Dir {
+        root:           String,
+        /// path and writer of the file currently being written
+        writer:         Option<(String, BufWriter<File>)>,
+        staging:        Option<Staging>,
+        /// leave existing files alone if their content wouldn't change
+        keep_unchanged: bool,
+    },
+    Zip {
+        root:     String,
+        zip_path: String,
+        writer:   Box<ZipWriter<BufWriter<File>>>,
+        /// the entries are deflated on threads of their own
+        deflater: Deflater,
+        staging:  Option<Staging>,
+    },
+}
+
+impl Output {
+    /// write files into `out_dir`, staged in a temporary directory if `atomic`
+    pub fn dir(out_dir: &str, atomic: bool) -> Result<Self, SplitError> {
+        let staging = if atomic {
+            Some(Staging::dir(Path::new(out_dir))?)
+        } else {
+            // ensure that out_dir exists
+            create_dir_all(out_dir).create_context(out_dir)?;
+            None
+        };
+        Ok(Output::Dir {
+            root:           out_dir.to_string(),
+            writer:         None,
+            staging,
+            keep_unchanged: false,
+        })
+    }
+
+    /// write files into a new zip file at `zip_path` (written to a temporary
+    /// file first if `atomic`); every entry is placed under a top-level
+    /// directory named after the zip file's stem
+    pub fn zip(zip_path: &Path, atomic: bool) -> Result<Self, SplitError> {
+        let display = zip_path.display().to_string();
+        let zip_parent_dir: String = zip_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let staging = if atomic {
+            Some(Staging::file(zip_path)?)
+        } else {
+            None
+        };
+        let zipfile = match staging.as_ref() {
+            Some(staging) => File::create(&staging.path),
+            None          => File::create(zip_path),
+        }.create_context(&display)?;
+        let mut zip_writer = ZipWriter::new(BufWriter::new(zipfile));
+        zip_writer.add_directory(
+            &zip_parent_dir,
+            zip::write::FileOptions::default())
+            .map_err(|source| SplitError::Zip { path: display.to_owned(), source })?;
+        Ok(Output::Zip {
+            root:     zip_parent_dir,
+            zip_path: display,
+            writer:   Box::new(zip_writer),
+            deflater: Deflater::new(),
+            staging,
+        })
+    }
+
+    /// remove the given folders (relative to the output root) from an output
+    /// directory: when staging, this is done just before the staged output
+    /// is moved into place, otherwise immediately
+    pub fn clean(&mut self, folders: &[String]) -> Result<(), SplitError> {
+        match self {
+            Output::Dir { staging: Some(staging), .. } => {
+                let target = staging.target.to_owned();
+                staging.clean.extend(folders.iter().map(|f| target.join(f)));
+            },
+            Output::Dir { root, staging: None, .. } => {
+                for folder in folders {
+                    remove_folder(&Path::new(root).join(folder))?;
+                }
+            },
+            // zip files are always created from scratch
+            Output::Zip { .. } => {},
+        }
+        Ok(())
+    }
+
+    /// don't rewrite files that already exist with the same content (see
+    /// `write_file`); zip files are always created from scratch, so this
+    /// only applies to output directories
+    pub fn keep_unchanged(&mut self, keep: bool) {
+        if let Output::Dir { keep_unchanged, .. } = self {
+            *keep_unchanged = keep;
+        }
+    }
+
+    /// directory that output paths should be built relative to
+    pub fn root(&self) -> &str {
+        match self {
+            Output::Dir { root, .. } => root,
+            Output::Zip { root, .. } => root,
+        }
+    }
+
+    /// `path` relative to the output root
+    pub fn relative_path<'a>(&self, path: &'a str) -> &'a str {
+        path.strip_prefix(self.root())
+            .and_then(|p| p.strip_prefix('/'))
+            .unwrap_or(path)
+    }
+
+    /// write a complete file named `name` into the output root
+    pub fn write_root_file(&mut self, name: &str, contents: &[u8]) -> Result<(), SplitError> {
+        let path = format!("{}/{}", self.root(), name);
+        self.write_file(&path, contents, OnExisting::Overwrite).map(|_| ())
+    }
+
+    /// write a complete file at `path`, unless it's left untouched because
+    /// it already exists with the same content (only when keeping unchanged
+    /// files) or because of `on_existing`; entries of a zip file are handed
+    /// to its deflater, and only added to it once they're deflated
+    pub fn write_file(
+        &mut self,
+        path:        &str,
+        contents:    &[u8],
+        on_existing: OnExisting,
+    ) -> Result<Written, SplitError> {
+        if let Output::Dir { keep_unchanged, .. } = self {
+            let existing = Path::new(path);
+            let unchanged = has_contents(existing, contents);
+            if *keep_unchanged && unchanged {
+                return Ok(Written::Unchanged);
+            }
+            // rewriting an identical file doesn't lose anything
+            if existing.is_file() && !unchanged {
+                match on_existing {
+                    OnExisting::Overwrite => (),
+                    OnExisting::Skip      => return Ok(Written::Skipped),
+                    OnExisting::Error     => return Err(SplitError::OutputExists(path.to_string())),
+                    OnExisting::Backup    => {
+                        let backup = format!("{}.bak", path);
+                        let physical = self.physical_path(&backup);
+                        if let Some(dir) = physical.parent() {
+                            create_dir_all(dir).create_context(&dir.display().to_string())?;
+                        }
+                        fs::copy(existing, physical).write_context(&backup)?;
+                    },
+                }
+            }
+        }
+        if let Output::Zip { writer, deflater, zip_path, .. } = self {
+            deflater.add(writer, path, contents)
+                .map_err(|source| SplitError::Zip { path: zip_path.to_owned(), source })?;
+            return Ok(Written::Created);
+        }
+        self.start_file(path)?;
+        self.write(contents)?;
+        self.close_file()?;
+        Ok(Written::Created)
+    }
+
+    /// flush and close the current file, so it's complete on disk
+    pub fn close_file(&mut self) -> Result<(), SplitError> {
+        if let Output::Dir { writer, .. } = self {
+            if let Some((path, mut w)) = writer.take() {
+                w.flush().write_context(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// where the file for `path` is actually written
+    fn physical_path(&self, path: &str) -> PathBuf {
+        match self {
+            Output::Dir { staging: Some(staging), .. } =>
+                staging.path.join(self.relative_path(path)),
+            _ => PathBuf::from(path),
+        }
+    }
+
+    /// close the current file (if any) and start writing to `path`, in an
+    /// output directory (zip entries are written whole by `write_file`)
+    pub fn start_file(&mut self, path: &str) -> Result<(), SplitError> {
+        let physical = self.physical_path(path);
+        if let Output::Dir { writer, .. } = self {
+            if let Some((previous, mut w)) = writer.take() {
+                w.flush().write_context(&previous)?;
+            }
+            // ensure that dir exists
+            if let Some(dir) = physical.parent() {
+                create_dir_all(dir).create_context(&dir.display().to_string())?;
+            }
+            let file = File::create(&physical).create_context(path)?;
+            *writer = Some((path.to_string(), BufWriter::new(file)));
+        }
+        Ok(())
+    }
+
+    /// stop writing to the current file, e.g. after it failed; further writes
+    /// are discarded until the next file is started
+    pub fn abandon_file(&mut self) {
+        if let Output::Dir { writer, .. } = self {
+            *writer = None;
+        }
+    }
+
+    /// write to the current file; data is discarded if no file has been
+    /// started yet
+    pub fn write(&mut self, buf: &[u8]) -> Result<(), SplitError> {
+        if let Output::Dir { writer, .. } = self {
+            if let Some((path, w)) = writer.as_mut() {
+                w.write_all(buf).write_context(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// flush the current file and, for zip files, add the entries that are
+    /// still being deflated and write the central directory; staged output
+    /// is then moved into place
+    pub fn finish(self) -> Result<(), SplitError> {
+        let staging = match self {
+            Output::Dir { writer, staging, .. } => {
+                if let Some((path, mut w)) = writer {
+                    w.flush().write_context(&path)?;
+                }
+                staging
+            },
+            Output::Zip { mut writer, deflater, zip_path, staging, .. } => {
+                let zip_error = |source| SplitError::Zip { path: zip_path.to_owned(), source };
+                deflater.finish(&mut writer).map_err(zip_error)?;
+                writer.finish()
+                    .map_err(zip_error)?
+                    .flush()
+                    .write_context(&zip_path)?;
+                drop(writer);
+                staging
+            },
+        };
+        match staging {
+            Some(staging) => staging.commit(),
+            None          => Ok(()),
+        }
+    }
+
+    /// whether a failure writing one file leaves the rest of the output
+    /// usable (a zip file is a single stream, so it doesn't)
+    pub fn can_recover(&self) -> bool {
+        matches!(self, Output::Dir { .. })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_dir_leaves_unchanged_files_alone() {
+        let scratch = Scratch::new("test-merge-dir");
+        let (from, to) = (scratch.0.join("from"), scratch.0.join("to"));
+        create_dir_all(from.join("Table")).unwrap();
+        create_dir_all(to.join("Table")).unwrap();
+        fs::write(from.join("Table/same.sql"), "same").unwrap();
+        fs::write(from.join("Table/changed.sql"), "new").unwrap();
+        fs::write(from.join("Table/added.sql"), "added").unwrap();
+        fs::write(to.join("Table/same.sql"), "same").unwrap();
+        fs::write(to.join("Table/changed.sql"), "old").unwrap();
+        fs::write(to.join("Table/other.sql"), "other").unwrap();
+        // an identical file that's left alone keeps its timestamp
+        let old = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000);
+        File::options().write(true).open(to.join("Table/same.sql")).unwrap().set_modified(old).unwrap();
+
+        merge_dir(&from, &to).unwrap();
+        assert_eq!(fs::metadata(to.join("Table/same.sql")).unwrap().modified().unwrap(), old);
+        assert_eq!(fs::read_to_string(to.join("Table/changed.sql")).unwrap(), "new");
+        assert_eq!(fs::read_to_string(to.join("Table/added.sql")).unwrap(), "added");
+        assert_eq!(fs::read_to_string(to.join("Table/other.sql")).unwrap(), "other");
+    }
+
+    #[test]
+    fn output_directories_are_staged_inside_themselves() {
+        let scratch = Scratch::new("test-staging");
+        let out_dir = scratch.0.join("out");
+        let root = out_dir.display().to_string();
+        let mut output = Output::dir(&root, true).unwrap();
+        assert!(out_dir.join(STAGING_DIR).is_dir());
+        output.write_file(&format!("{}/Table/dbo.T.sql", root), b"CREATE TABLE T (a int)", OnExisting::Overwrite).unwrap();
+        assert!(!out_dir.join("Table").exists());
+
+        output.finish().unwrap();
+        assert_eq!(fs::read_to_string(out_dir.join("Table/dbo.T.sql")).unwrap(), "CREATE TABLE T (a int)");
+        assert!(!out_dir.join(STAGING_DIR).exists());
+        // and nothing is left next to the output directory
+        let names: Vec<_> = fs::read_dir(&scratch.0).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(names, vec!["out"]);
+    }
+}
@@ -0,0 +1,242 @@
+/*
+ * the threads a split runs on besides the one scanning the input: one that
+ * reads (and decodes) the input ahead of the scanner, and a pool that
+ * deflates the entries of zip files, which are still added to them in the
+ * order they were written; they're connected by bounded channels, so neither
+ * gets far ahead of the scanner
+ */
+use std::collections::BTreeMap;
+use std::io::{ self, BufRead, Cursor, Read, Write };
+use std::sync::mpsc::{ Receiver, SyncSender, TryRecvError, sync_channel };
+use std::sync::{ Arc, Mutex };
+use std::thread::{ self, JoinHandle };
+use zip::result::{ ZipError, ZipResult };
+use zip::write::FileOptions;
+use zip::{ ZipArchive, ZipWriter };
+
+/// how much of the input is read at a time, and how many such chunks can be
+/// waiting for the scanner
+const CHUNK_SIZE: usize = 256 * 1024;
+const CHUNKS_AHEAD: usize = 16;
+
+/// a reader for `inner` that reads it on a thread of its own
+pub struct ReadAhead {
+    chunks: Receiver<io::Result<Vec<u8>>>,
+    chunk:  Vec<u8>,
+    /// how much of `chunk` has been consumed
+    at:     usize,
+}
+
+/// the next chunk of `inner`, which is empty at its end
+fn read_chunk(inner: &mut dyn BufRead) -> io::Result<Vec<u8>> {
+    let mut chunk = vec![0; CHUNK_SIZE];
+    let mut len = 0;
+    while len < chunk.len() {
+        match inner.read(&mut chunk[len..]) {
+            Ok(0)                                           => break,
+            Ok(n)                                           => len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e)                                          => return Err(e),
+        }
+    }
+    chunk.truncate(len);
+    Ok(chunk)
+}
+
+impl ReadAhead {
+    pub fn new(mut inner: Box<dyn BufRead + Send>) -> Self {
+        let (sender, chunks) = sync_channel(CHUNKS_AHEAD);
+        // the thread stops at the end of the input, after an error, or once
+        // the reader is dropped
+        thread::spawn(move || loop {
+            let chunk = read_chunk(&mut inner);
+            let done = !matches!(&chunk, Ok(chunk) if !chunk.is_empty());
+            if sender.send(chunk).is_err() || done {
+                break;
+            }
+        });
+        ReadAhead { chunks, chunk: Vec::new(), at: 0 }
+    }
+}
+
+impl Read for ReadAhead {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for ReadAhead {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.at >= self.chunk.len() {
+            // the thread is gone at the end of the input
+            self.chunk = self.chunks.recv().unwrap_or_else(|_| Ok(Vec::new()))?;
+            self.at = 0;
+        }
+        Ok(&self.chunk[self.at..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.at += amount;
+    }
+}
+
+/// an entry to deflate: its number (in the order they were added), name and
+/// contents
+type Entry = (usize, String, Vec<u8>);
+
+/// deflates the entries of a zip file on a pool of threads
+pub struct Deflater {
+    entries:  Option<SyncSender<Entry>>,
+    /// every entry, deflated on its own into a zip file with just that entry
+    deflated: Receiver<(usize, ZipResult<Vec<u8>>)>,
+    workers:  Vec<JoinHandle<()>>,
+    /// how many entries can be deflated (or waiting for the ones before
+    /// them) at a time
+    limit:    usize,
+    added:    usize,
+    /// the number of the next entry to add to the zip file
+    next:     usize,
+    /// deflated entries that are waiting for the ones before them
+    waiting:  BTreeMap<usize, ZipResult<Vec<u8>>>,
+}
+
+/// `contents` deflated, as a zip file with nothing but an entry `name`
+fn deflate(name: &str, contents: &[u8]) -> ZipResult<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file(name, FileOptions::default())?;
+    zip.write_all(contents)?;
+    Ok(zip.finish()?.into_inner())
+}
+
+impl Deflater {
+    /// a pool of a thread per CPU
+    pub fn new() -> Self {
+        let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        let limit = threads * 4;
+        let (entries, received) = sync_channel::<Entry>(threads);
+        let (sender, deflated) = sync_channel(limit);
+        let received = Arc::new(Mutex::new(received));
+        let workers = (0..threads)
+            .map(|_| {
+                let received = Arc::clone(&received);
+                let sender = sender.clone();
+                thread::spawn(move || loop {
+                    // the lock is only held while waiting for an entry
+                    let entry = received.lock().map(|received| received.recv());
+                    let Ok(Ok((number, name, contents))) = entry else {
+                        break;
+                    };
+                    if sender.send((number, deflate(&name, &contents))).is_err() {
+                        break;
+                    }
+                })
+            })
+            .collect();
+        Deflater {
+            entries: Some(entries),
+            deflated,
+            workers,
+            limit,
+            added:   0,
+            next:    0,
+            waiting: BTreeMap::new(),
+        }
+    }
+
+    /// deflate an entry `name` with `contents`, and add it to `zip` after
+    /// the ones added before it; the entries that are ready by then are
+    /// added to `zip` right away
+    pub fn add<W: Write + io::Seek>(&mut self, zip: &mut ZipWriter<W>, name: &str, contents: &[u8]) -> ZipResult<()> {
+        while self.added - self.next >= self.limit {
+            self.receive(zip)?;
+        }
+        self.entries.as_ref()
+            .and_then(|entries| entries.send((self.added, name.to_string(), contents.to_vec())).ok())
+            .ok_or_else(stopped)?;
+        self.added += 1;
+        loop {
+            match self.deflated.try_recv() {
+                Ok(deflated)                    => self.arrived(zip, deflated)?,
+                Err(TryRecvError::Empty)        => return Ok(()),
+                Err(TryRecvError::Disconnected) => return Err(stopped()),
+            }
+        }
+    }
+
+    /// add the rest of the entries to `zip`, once they're deflated
+    pub fn finish<W: Write + io::Seek>(mut self, zip: &mut ZipWriter<W>) -> ZipResult<()> {
+        while self.next < self.added {
+            self.receive(zip)?;
+        }
+        self.entries = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+        Ok(())
+    }
+
+    /// wait for an entry to be deflated
+    fn receive<W: Write + io::Seek>(&mut self, zip: &mut ZipWriter<W>) -> ZipResult<()> {
+        let deflated = self.deflated.recv().map_err(|_| stopped())?;
+        self.arrived(zip, deflated)
+    }
+
+    /// add the entries that are next to `zip`, now that `deflated` arrived
+    fn arrived<W: Write + io::Seek>(&mut self, zip: &mut ZipWriter<W>, (number, deflated): (usize, ZipResult<Vec<u8>>)) -> ZipResult<()> {
+        self.waiting.insert(number, deflated);
+        while let Some(deflated) = self.waiting.remove(&self.next) {
+            let mut entry = ZipArchive::new(Cursor::new(deflated?))?;
+            zip.raw_copy_file(entry.by_index_raw(0)?)?;
+            self.next += 1;
+        }
+        Ok(())
+    }
+}
+
+/// the error when the deflating threads are gone, which only happens if they
+/// panicked
+fn stopped() -> ZipError {
+    ZipError::Io(io::Error::other("a compression thread stopped"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_ahead_reads_everything_in_order() {
+        let input: Vec<u8> = (0..CHUNK_SIZE * 3 + 17).map(|i| (i % 251) as u8).collect();
+        let mut read = Vec::new();
+        ReadAhead::new(Box::new(Cursor::new(input.clone()))).read_to_end(&mut read).unwrap();
+        assert_eq!(read, input);
+    }
+
+    #[test]
+    fn deflated_entries_round_trip_in_order() {
+        let entries: Vec<(String, String)> = (0..100)
+            .map(|i| (format!("root/Table/dbo.T{}.sql", i), format!("CREATE TABLE [dbo].[T{}] (a int)\nGO\n", i).repeat(i + 1)))
+            .collect();
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let mut deflater = Deflater::new();
+        for (name, contents) in entries.iter() {
+            deflater.add(&mut zip, name, contents.as_bytes()).unwrap();
+        }
+        deflater.finish(&mut zip).unwrap();
+        let zipped = zip.finish().unwrap().into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(zipped)).unwrap();
+        assert_eq!(archive.len(), entries.len());
+        for (index, (name, contents)) in entries.iter().enumerate() {
+            let mut entry = archive.by_index(index).unwrap();
+            assert_eq!(entry.name(), name);
+            assert_eq!(entry.compression(), zip::CompressionMethod::Deflated);
+            let mut read = String::new();
+            entry.read_to_string(&mut read).unwrap();
+            assert_eq!(&read, contents);
+        }
+    }
+}
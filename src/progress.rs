@@ -0,0 +1,28 @@
+/*
+ * progress reporting for long-running splits
+ */
+use indicatif::{ ProgressBar, ProgressStyle };
+use std::io::IsTerminal;
+
+/// create a progress bar on stderr tracking bytes read from the input; `len`
+/// is the size of the input when it is known (i.e. not reading from stdin).
+/// the bar is hidden when not `enabled` or when stderr is not a terminal
+pub fn progress_bar(len: Option<u64>, enabled: bool) -> ProgressBar {
+    if !enabled || !std::io::stderr().is_terminal() {
+        return ProgressBar::hidden();
+    }
+    match len {
+        Some(len) => {
+            let style = ProgressStyle::with_template(
+                "{bar:40} {bytes}/{total_bytes} ({bytes_per_sec}, eta {eta}) {wide_msg}")
+                .expect("invalid progress bar template");
+            ProgressBar::new(len).with_style(style)
+        },
+        None => {
+            let style = ProgressStyle::with_template(
+                "{spinner} {bytes} ({bytes_per_sec}) {wide_msg}")
+                .expect("invalid progress bar template");
+            ProgressBar::new_spinner().with_style(style)
+        },
+    }
+}
@@ -0,0 +1,96 @@
+/*
+ * per-object metadata collected while splitting
+ */
+use crate::error::SplitError;
+use clap::ValueEnum;
+use serde::Serialize;
+use sha2::{ Digest, Sha256 };
+
+#[derive(Clone, Serialize)]
+pub struct ObjectRecord {
+    #[serde(rename = "type")]
+    pub object_type:  String,
+    pub schema:       String,
+    pub name:         String,
+    /// database named in the object header or else by the most recent USE
+    /// statement (empty if there was neither)
+    pub database:     String,
+    /// the number of a numbered procedure (`[name];N`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub number:       Option<u32>,
+    pub path:         String,
+    /// offset (in decoded bytes) of the object header in the input
+    pub start_offset: usize,
+    /// offset (in decoded bytes) just past the last line of the object
+    pub end_offset:   usize,
+    /// number of lines written to the output file
+    pub lines:        usize,
+    /// number of bytes written to the output file
+    pub bytes:        usize,
+    /// number of batches the object's script runs, i.e. its GO separators
+    /// (counting repeat counts such as `GO 5`)
+    pub batches:      usize,
+    /// hex-encoded sha256 of the output file's content
+    pub sha256:       String,
+    #[serde(skip)]
+    hasher:           Sha256,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum EventFormat {
+    Ndjson,
+}
+
+impl ObjectRecord {
+    pub fn new(
+        object_type: String,
+        schema:      String,
+        name:        String,
+        database:    String,
+        path:        String,
+        offset:      usize,
+    ) -> Self {
+        ObjectRecord {
+            object_type,
+            schema,
+            name,
+            database,
+            number:       None,
+            path,
+            start_offset: offset,
+            end_offset:   offset,
+            lines:        0,
+            bytes:        0,
+            batches:      0,
+            sha256:       String::new(),
+            hasher:       Sha256::new(),
+        }
+    }
+
+    /// account for `buf` having been written to the object's output file
+    pub fn add(&mut self, buf: &[u8]) {
+        self.lines += buf.iter().filter(|&&b| b == b'\n').count();
+        self.bytes += buf.len();
+        self.hasher.update(buf);
+    }
+
+    /// finalize the content hash; called once nothing more will be written
+    pub fn finish(&mut self) {
+        self.sha256 = self.hasher.finalize_reset()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+    }
+
+    /// print the record to stdout as a single event in the given format
+    pub fn emit(&self, format: EventFormat) -> Result<(), SplitError> {
+        match format {
+            EventFormat::Ndjson => {
+                let json = serde_json::to_string(self)
+                    .map_err(|source| SplitError::Serialize { what: "event", source })?;
+                println!("{}", json);
+            },
+        }
+        Ok(())
+    }
+}
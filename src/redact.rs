@@ -0,0 +1,276 @@
+/*
+ * --redact: mask the values of chosen columns in scripted INSERT statements,
+ * so that scripts of production data can be used elsewhere
+ */
+use crate::error::SplitError;
+use regex::Regex;
+use serde::Deserialize;
+use sha2::{ Digest, Sha256 };
+use std::fs;
+use std::ops::Range;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Rules {
+    #[serde(default, rename = "rule")]
+    rules: Vec<Rule>,
+}
+
+/// a column to redact, as a [[rule]] table
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Rule {
+    /// `schema.table`
+    table:  String,
+    column: String,
+    mask:   Mask,
+    /// the replacement, for the fixed mask
+    value:  Option<String>,
+}
+
+#[derive(Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Mask {
+    // the same value for every row
+    Fixed,
+    // a hash of the value, which keeps equal values equal
+    Hash,
+    // NULL
+    Null,
+}
+
+pub struct Redactor {
+    rules:  Vec<Rule>,
+    /// matches the start of an INSERT statement up to its VALUES
+    insert: Regex,
+}
+
+/// `name` without the brackets around it, if any
+fn unquote(name: &str) -> String {
+    let name = name.trim();
+    match name.strip_prefix('[').and_then(|n| n.strip_suffix(']')) {
+        Some(inner) => inner.replace("]]", "]"),
+        None        => name.to_string(),
+    }
+}
+
+/// a T-SQL string literal for `s`
+fn literal(s: &str) -> String {
+    format!("N'{}'", s.replace('\'', "''"))
+}
+
+/// the rows of a VALUES list starting at `at`, as the ranges of their values,
+/// along with where the list ends; the rows are left out if the list can't
+/// be parsed
+fn rows(s: &str, mut at: usize) -> (Vec<Vec<Range<usize>>>, usize) {
+    let bytes = s.as_bytes();
+    let mut rows = Vec::new();
+    loop {
+        if bytes.get(at) != Some(&b'(') {
+            return (rows, at);
+        }
+        at += 1;
+        let mut row = Vec::new();
+        let mut start = at;
+        let mut depth = 0;
+        loop {
+            match bytes.get(at) {
+                None => return (Vec::new(), at),
+                Some(b'\'') => {
+                    // a string, in which '' is a quote
+                    at += 1;
+                    loop {
+                        match bytes.get(at) {
+                            None                                             => return (Vec::new(), at),
+                            Some(b'\'') if bytes.get(at + 1) == Some(&b'\'') => at += 2,
+                            Some(b'\'')                                      => break,
+                            Some(_)                                          => at += 1,
+                        }
+                    }
+                },
+                Some(b'(') => depth += 1,
+                Some(b')') if depth > 0 => depth -= 1,
+                Some(b')') => {
+                    row.push(start..at);
+                    at += 1;
+                    break;
+                },
+                Some(b',') if depth == 0 => {
+                    row.push(start..at);
+                    start = at + 1;
+                },
+                Some(_) => (),
+            }
+            at += 1;
+        }
+        rows.push(row);
+
+        // another row may follow
+        let rest = &s[at..];
+        let trimmed = rest.trim_start();
+        match trimmed.strip_prefix(',') {
+            Some(next) => at += rest.len() - next.trim_start().len(),
+            None       => return (rows, at),
+        }
+    }
+}
+
+impl Redactor {
+    /// the rules in the TOML file at `path`
+    pub fn load(path: &str) -> Result<Self, SplitError> {
+        let text = fs::read_to_string(path)
+            .map_err(|source| SplitError::OpenInput { path: path.to_owned(), source })?;
+        Redactor::parse(path, &text)
+    }
+
+    /// the rules in `text`, read from `path`
+    fn parse(path: &str, text: &str) -> Result<Self, SplitError> {
+        let Rules { rules } = toml::from_str(text)
+            .map_err(|e| SplitError::Config { path: path.to_owned(), message: e.to_string() })?;
+        if let Some(rule) = rules.iter().find(|rule| (rule.mask == Mask::Fixed) != rule.value.is_some()) {
+            return Err(SplitError::Config {
+                path:    path.to_owned(),
+                message: format!("{}.{}: a value is needed for the fixed mask, and only for it", rule.table, rule.column),
+            });
+        }
+        Ok(Redactor {
+            rules,
+            insert: Regex::new(r"(?im)^[ \t]*INSERT\s+(?:INTO\s+)?(\[(?:[^\]]|\]\])+\]|\w+)\.(\[(?:[^\]]|\]\])+\]|\w+)\s*\(([^)]*)\)\s*VALUES\s*")
+                .expect("error compiling INSERT regular expression"),
+        })
+    }
+
+    /// what `value`, a literal, is replaced by
+    fn mask(rule: &Rule, value: &str) -> String {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("NULL") {
+            return value.to_string();
+        }
+        match rule.mask {
+            Mask::Fixed => literal(rule.value.as_deref().unwrap_or_default()),
+            Mask::Hash  => {
+                let hash = Sha256::digest(value.as_bytes());
+                literal(&format!("{:x}", hash)[..16])
+            },
+            Mask::Null  => String::from("NULL"),
+        }
+    }
+
+    /// `content` with the values of the redacted columns masked
+    pub fn apply(&self, content: &str) -> String {
+        let mut masked = String::with_capacity(content.len());
+        let mut copied = 0;
+        let mut from = 0;
+        while let Some(caps) = self.insert.captures_at(content, from) {
+            let statement = caps.get(0).unwrap();
+            let table = format!("{}.{}", unquote(&caps[1]), unquote(&caps[2]));
+            let columns: Vec<String> = caps[3].split(',').map(unquote).collect();
+            let (rows, end) = rows(content, statement.end());
+            from = end.max(statement.end());
+
+            // the column number each rule for this table applies to, in the
+            // order the values appear
+            let mut rules: Vec<(usize, &Rule)> = self.rules.iter()
+                .filter(|rule| rule.table.eq_ignore_ascii_case(&table))
+                .filter_map(|rule| columns.iter()
+                    .position(|column| column.eq_ignore_ascii_case(&rule.column))
+                    .map(|index| (index, rule)))
+                .collect();
+            rules.sort_by_key(|(index, _)| *index);
+            rules.dedup_by_key(|(index, _)| *index);
+            for row in rows.iter() {
+                for (index, rule) in rules.iter() {
+                    if let Some(range) = row.get(*index) {
+                        masked.push_str(&content[copied..range.start]);
+                        let value = &content[range.clone()];
+                        let leading = value.len() - value.trim_start().len();
+                        let trailing = value.len() - value.trim_end().len();
+                        masked.push_str(&value[..leading]);
+                        masked.push_str(&Redactor::mask(rule, value));
+                        masked.push_str(&value[value.len() - trailing..]);
+                        copied = range.end;
+                    }
+                }
+            }
+        }
+        masked.push_str(&content[copied..]);
+        masked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redactor(rules: &str) -> Redactor {
+        Redactor::parse("<test>", rules).expect("rules should parse")
+    }
+
+    #[test]
+    fn strings_with_quotes_in_them_are_masked_whole() {
+        let redactor = redactor(r#"
+            [[rule]]
+            table  = "dbo.Users"
+            column = "Email"
+            mask   = "fixed"
+            value  = "x@example.com"
+        "#);
+        let content = "INSERT [dbo].[Users] ([Id], [Name], [Email]) VALUES (1, N'O''Brien, Pat', N'pat.o''brien@corp.com')\nGO\n";
+        assert_eq!(
+            redactor.apply(content),
+            "INSERT [dbo].[Users] ([Id], [Name], [Email]) VALUES (1, N'O''Brien, Pat', N'x@example.com')\nGO\n"
+        );
+    }
+
+    #[test]
+    fn unicode_strings_are_hashed_like_their_text() {
+        let redactor = redactor(r#"
+            [[rule]]
+            table  = "dbo.Users"
+            column = "Name"
+            mask   = "hash"
+        "#);
+        let masked = redactor.apply("INSERT INTO dbo.Users (Id, Name) VALUES (1, N'Zoë'), (2, N'Zoë'), (3, N'')\n");
+        let hashes: Vec<&str> = masked.split("N'").skip(1).map(|rest| &rest[..16]).collect();
+        assert_eq!(hashes.len(), 3);
+        assert_eq!(hashes[0], hashes[1]);
+        assert_ne!(hashes[0], hashes[2]);
+        assert!(!masked.contains("Zoë"));
+    }
+
+    #[test]
+    fn nulls_are_left_alone() {
+        let redactor = redactor(r#"
+            [[rule]]
+            table  = "dbo.Users"
+            column = "Phone"
+            mask   = "fixed"
+            value  = "000"
+        "#);
+        assert_eq!(
+            redactor.apply("INSERT [dbo].[Users] ([Id], [Phone]) VALUES (1, NULL), (2, N'555-0100')\n"),
+            "INSERT [dbo].[Users] ([Id], [Phone]) VALUES (1, NULL), (2, N'000')\n"
+        );
+    }
+
+    #[test]
+    fn every_row_of_a_values_list_is_masked() {
+        let redactor = redactor(r#"
+            [[rule]]
+            table  = "dbo.Users"
+            column = "Email"
+            mask   = "null"
+
+            [[rule]]
+            table  = "dbo.Users"
+            column = "Name"
+            mask   = "fixed"
+            value  = "someone"
+        "#);
+        let content = "INSERT [dbo].[Users] ([Id], [Name], [Email], [Created])\nVALUES (1, N'Ann', N'ann@corp.com', CAST(N'2026-01-01' AS Date)),\n       (2, N'Bob', N'bob@corp.com', GETDATE())\nINSERT [dbo].[Orders] ([Id], [Email]) VALUES (1, N'kept@corp.com')\n";
+        assert_eq!(
+            redactor.apply(content),
+            "INSERT [dbo].[Users] ([Id], [Name], [Email], [Created])\nVALUES (1, N'someone', NULL, CAST(N'2026-01-01' AS Date)),\n       (2, N'someone', NULL, GETDATE())\nINSERT [dbo].[Orders] ([Id], [Email]) VALUES (1, N'kept@corp.com')\n"
+        );
+    }
+}
@@ -0,0 +1,59 @@
+/*
+ * the script subcommand: script the objects of a database from a live
+ * server, and split them as if they had been exported with SSMS
+ */
+use crate::cli::Cli;
+use crate::diagnostics::Diagnostics;
+use crate::error::{ IoContext, SplitError };
+use crate::output::Scratch;
+use crate::server::Connection;
+use crate::splitter::split;
+use std::fs;
+
+/// the definitions of the database's modules (procedures, views, functions
+/// and triggers), each with an SSMS-style header and the SET options it was
+/// created with, after a USE of the database
+pub const MODULES: &str = "SET NOCOUNT ON;
+DECLARE @nl nchar(2) = CHAR(13) + CHAR(10);
+SELECT N'USE ' + QUOTENAME(DB_NAME()) + @nl + N'GO' + @nl;
+SELECT N'/****** Object:  ' + m.object_type + N' ' + m.object_name + N' ******/' + @nl
+     + N'SET ANSI_NULLS ' + CASE WHEN m.uses_ansi_nulls = 1 THEN N'ON' ELSE N'OFF' END + @nl + N'GO' + @nl
+     + N'SET QUOTED_IDENTIFIER ' + CASE WHEN m.uses_quoted_identifier = 1 THEN N'ON' ELSE N'OFF' END + @nl + N'GO' + @nl
+     + m.definition + @nl + N'GO' + @nl
+FROM (
+    SELECT CASE o.type WHEN 'P' THEN N'StoredProcedure'
+                       WHEN 'V' THEN N'View'
+                       WHEN 'TR' THEN N'Trigger'
+                       ELSE N'UserDefinedFunction' END AS object_type,
+           QUOTENAME(SCHEMA_NAME(o.schema_id)) + N'.' + QUOTENAME(o.name) AS object_name,
+           sm.uses_ansi_nulls, sm.uses_quoted_identifier, sm.definition
+    FROM sys.sql_modules sm
+    JOIN sys.objects o ON o.object_id = sm.object_id
+    WHERE o.is_ms_shipped = 0 AND o.type IN ('P', 'V', 'TR', 'FN', 'IF', 'TF')
+    UNION ALL
+    SELECT N'DdlTrigger', QUOTENAME(t.name),
+           sm.uses_ansi_nulls, sm.uses_quoted_identifier, sm.definition
+    FROM sys.sql_modules sm
+    JOIN sys.triggers t ON t.object_id = sm.object_id
+    WHERE t.parent_class = 0 AND t.is_ms_shipped = 0
+) m
+WHERE m.definition IS NOT NULL
+ORDER BY m.object_type, m.object_name;";
+
+/// script the database `connection` points to and split it as described by
+/// `cli`
+pub fn script(cli: &Cli, connection: &Connection, diagnostics: &mut Diagnostics) -> Result<(), SplitError> {
+    let script = connection.query(MODULES)?;
+    let scratch = Scratch::new("script");
+    let path = scratch.0.join("script.sql");
+    let display = path.display().to_string();
+    fs::create_dir_all(&scratch.0).create_context(&display)?;
+    fs::write(&path, script).write_context(&display)?;
+
+    let cli = Cli {
+        in_file:      Some(display),
+        windows_1252: false,
+        ..cli.clone()
+    };
+    split(&cli, &connection.describe(), diagnostics)
+}
@@ -0,0 +1,284 @@
+/*
+ * connections to a SQL Server (script, apply, diff-live), made with the
+ * tiberius TDS client on a single-threaded tokio runtime
+ */
+use crate::error::SplitError;
+use crate::tokenizer::batches;
+use std::fs;
+use std::path::Path;
+use tiberius::{ AuthMethod, Client, Config, SqlBrowser };
+use tokio::net::TcpStream;
+use tokio::runtime::Runtime;
+use tokio_util::compat::{ Compat, TokioAsyncWriteCompatExt };
+
+/// the options every connection starts with, as sqlcmd (through ODBC) sets
+/// them; e.g. indexed views can't be created without them
+const SESSION_OPTIONS: &str = "SET ANSI_NULLS, ANSI_PADDING, ANSI_WARNINGS, ARITHABORT, CONCAT_NULL_YIELDS_NULL, QUOTED_IDENTIFIER ON;
+SET NUMERIC_ROUNDABORT OFF;";
+
+/// where to connect to, from an ADO.NET-style connection string, e.g.
+/// `Server=db1;Database=Sales;Integrated Security=true`
+pub struct Connection {
+    server:            String,
+    database:          Option<String>,
+    /// SQL Server authentication, rather than integrated (Windows)
+    user:              Option<String>,
+    password:          Option<String>,
+    trust_certificate: bool,
+    /// what the client's I/O runs on
+    runtime:           Runtime,
+}
+
+/// the `key=value` pairs of `connection`, with lowercased keys; a value can
+/// be quoted with `"`, `'` or `{}` to contain a `;`
+fn pairs(connection: &str) -> Result<Vec<(String, String)>, SplitError> {
+    let mut pairs = Vec::new();
+    let mut rest = connection.trim_start();
+    while !rest.is_empty() {
+        let (key, after) = rest.split_once('=')
+            .ok_or_else(|| SplitError::BadConnection(format!("expected key=value in \"{}\"", rest)))?;
+        let after = after.trim_start();
+        let close = match after.chars().next() {
+            Some('{')              => Some('}'),
+            Some(q @ ('"' | '\'')) => Some(q),
+            _                      => None,
+        };
+        let (value, next) = match close {
+            Some(close) => {
+                let end = after[1..].find(close)
+                    .ok_or_else(|| SplitError::BadConnection(format!("unterminated value for {}", key.trim())))?;
+                let next = after[end + 2..].trim_start();
+                (&after[1..end + 1], next.strip_prefix(';').unwrap_or(next))
+            },
+            None => match after.split_once(';') {
+                Some((value, next)) => (value.trim_end(), next),
+                None                => (after.trim_end(), ""),
+            },
+        };
+        pairs.push((key.trim().to_lowercase(), value.to_string()));
+        rest = next.trim_start();
+    }
+    Ok(pairs)
+}
+
+fn yes(value: &str) -> bool {
+    ["true", "yes", "sspi"].contains(&value.to_lowercase().as_str())
+}
+
+/// the host, port and instance name of `server`, e.g. `tcp:db1,1433` or
+/// `db1\\SQLEXPRESS`; `.` and `(local)` are this machine
+fn address(server: &str) -> Result<(String, Option<u16>, Option<String>), SplitError> {
+    let server = server.trim();
+    let server = server.strip_prefix("tcp:").unwrap_or(server);
+    let (server, port) = match server.rsplit_once(',') {
+        Some((server, port)) => {
+            let port = port.trim().parse()
+                .map_err(|_| SplitError::BadConnection(format!("invalid port: {}", port.trim())))?;
+            (server, Some(port))
+        },
+        None => (server, None),
+    };
+    let (host, instance) = match server.split_once('\\') {
+        Some((host, instance)) => (host, Some(instance.to_string())),
+        None                   => (server, None),
+    };
+    let host = match host {
+        "." | "(local)" | "(localdb)" => "localhost",
+        host                          => host,
+    };
+    Ok((host.to_string(), port, instance))
+}
+
+impl Connection {
+    pub fn parse(connection: &str) -> Result<Self, SplitError> {
+        let mut server = None;
+        let mut database = None;
+        let mut user = None;
+        let mut password = None;
+        let mut integrated = false;
+        let mut trust_certificate = false;
+        for (key, value) in pairs(connection)? {
+            match key.as_str() {
+                "server" | "data source" | "address" | "addr"  => server = Some(value),
+                "database" | "initial catalog"                 => database = Some(value),
+                "user id" | "uid" | "user"                     => user = Some(value),
+                "password" | "pwd"                             => password = Some(value),
+                "integrated security" | "trusted_connection"   => integrated = yes(&value),
+                "trustservercertificate"                       => trust_certificate = yes(&value),
+                _ => return Err(SplitError::BadConnection(format!("unsupported connection string key: {}", key))),
+            }
+        }
+        if integrated {
+            user = None;
+        }
+        let server = server.ok_or_else(|| SplitError::BadConnection(String::from("the connection string has no Server")))?;
+        address(&server)?;
+        if user.is_none() && !cfg!(windows) {
+            return Err(SplitError::BadConnection(String::from(
+                "integrated security is only supported on Windows; give a User Id and Password")));
+        }
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| SplitError::Server(format!("failed to start the client: {}", e)))?;
+        Ok(Connection {
+            server,
+            database,
+            user,
+            password,
+            trust_certificate,
+            runtime,
+        })
+    }
+
+    /// the server and database, for messages
+    pub fn describe(&self) -> String {
+        match self.database.as_ref() {
+            Some(database) => format!("{}/{}", self.server, database),
+            None           => self.server.to_owned(),
+        }
+    }
+
+    /// the client's configuration
+    fn config(&self) -> Result<Config, SplitError> {
+        let (host, port, instance) = address(&self.server)?;
+        let mut config = Config::new();
+        config.host(host);
+        if let Some(port) = port {
+            config.port(port);
+        }
+        if let Some(instance) = instance {
+            config.instance_name(instance);
+        }
+        if let Some(database) = self.database.as_ref() {
+            config.database(database);
+        }
+        config.application_name("sql-splitter");
+        if self.trust_certificate {
+            config.trust_cert();
+        }
+        match self.user.as_ref() {
+            Some(user) => config.authentication(AuthMethod::sql_server(user, self.password.as_deref().unwrap_or_default())),
+            #[cfg(windows)]
+            None       => config.authentication(AuthMethod::Integrated),
+            // refused by `parse`
+            #[cfg(not(windows))]
+            None       => (),
+        }
+        Ok(config)
+    }
+
+    /// a new connection to the server, with the options sqlcmd would set
+    async fn connect(&self) -> Result<Client<Compat<TcpStream>>, SplitError> {
+        let failed = |e: &dyn std::fmt::Display| SplitError::Server(format!("couldn't connect to {}: {}", self.describe(), e));
+        let mut config = self.config()?;
+        // a named instance's port is looked up with the SQL Server Browser
+        let named = address(&self.server)?.2.is_some();
+        let mut redirected = false;
+        let mut client = loop {
+            let tcp = if named && !redirected {
+                TcpStream::connect_named(&config).await.map_err(|e| failed(&e))?
+            } else {
+                TcpStream::connect(config.get_addr()).await.map_err(|e| failed(&e))?
+            };
+            tcp.set_nodelay(true).map_err(|e| failed(&e))?;
+            match Client::connect(config.clone(), tcp.compat_write()).await {
+                Ok(client) => break client,
+                // Azure SQL Database may send the client on to another host,
+                // once
+                Err(tiberius::error::Error::Routing { host, port }) if !redirected => {
+                    config.host(host);
+                    config.port(port);
+                    redirected = true;
+                },
+                Err(e) => return Err(failed(&e)),
+            }
+        };
+        client.simple_query(SESSION_OPTIONS).await
+            .map_err(|e| failed(&e))?
+            .into_results().await
+            .map_err(|e| failed(&e))?;
+        Ok(client)
+    }
+
+    /// fail unless the server can be connected to, e.g. before running files
+    /// one by one, each of which would fail on its own otherwise
+    pub fn check(&self) -> Result<(), SplitError> {
+        self.runtime.block_on(async {
+            self.connect().await?.close().await
+                .map_err(|e| SplitError::Server(format!("couldn't connect to {}: {}", self.describe(), e)))
+        })
+    }
+
+    /// run `sql`, returning the values of its result sets, which have to be
+    /// strings, one row per line (and one column after the other)
+    pub fn query(&self, sql: &str) -> Result<String, SplitError> {
+        let failed = |e: tiberius::error::Error| SplitError::Server(format!("query on {} failed: {}", self.describe(), e));
+        self.runtime.block_on(async {
+            let mut client = self.connect().await?;
+            let results = client.simple_query(sql).await
+                .map_err(failed)?
+                .into_results().await
+                .map_err(failed)?;
+            let mut values = String::new();
+            for row in results.iter().flatten() {
+                for index in 0..row.len() {
+                    let value: Option<&str> = row.try_get(index).map_err(failed)?;
+                    values.push_str(value.unwrap_or_default());
+                }
+                values.push('\n');
+            }
+            Ok(values)
+        })
+    }
+
+    /// run the script at `path` on a connection of its own, batch by batch
+    /// (as separated by GO), stopping at the first error
+    pub fn run_file(&self, path: &Path) -> Result<(), SplitError> {
+        let display = path.display().to_string();
+        let script = fs::read_to_string(path)
+            .map_err(|source| SplitError::OpenInput { path: display.to_owned(), source })?;
+        let (batches, _) = batches(script.strip_prefix('\u{feff}').unwrap_or(&script));
+        self.runtime.block_on(async {
+            let mut client = self.connect().await?;
+            for batch in batches.iter().filter(|batch| !batch.sql.trim().is_empty()) {
+                for _ in 0..batch.count {
+                    let failed = |e| SplitError::Server(format!("line {}: {}", batch.line, e));
+                    client.simple_query(batch.sql.as_str()).await
+                        .map_err(failed)?
+                        .into_results().await
+                        .map_err(failed)?;
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_have_an_optional_port_and_instance() {
+        assert_eq!(address("db1").unwrap(), (String::from("db1"), None, None));
+        assert_eq!(address("tcp:db1.corp.local,14330").unwrap(), (String::from("db1.corp.local"), Some(14330), None));
+        assert_eq!(address(".\\SQLEXPRESS").unwrap(), (String::from("localhost"), None, Some(String::from("SQLEXPRESS"))));
+        assert!(address("db1,port").is_err());
+    }
+
+    #[test]
+    fn connection_strings_take_quoted_values() {
+        let connection = Connection::parse("Server=db1;Database={Sales;2026};User Id=app;Password='p;w'").unwrap();
+        assert_eq!(connection.describe(), "db1/Sales;2026");
+        assert_eq!(connection.password.as_deref(), Some("p;w"));
+        assert!(Connection::parse("Server=db1;User Id=app;Timeout=5").is_err());
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn integrated_security_needs_windows() {
+        assert!(Connection::parse("Server=db1;Integrated Security=true").is_err());
+        assert!(Connection::parse("Server=db1;User Id=app;Password=pw;Trusted_Connection=yes").is_err());
+    }
+}
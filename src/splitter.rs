@@ -0,0 +1,1634 @@
+/*
+ * reads the input line by line and splits it into one file per object
+ */
+use crate::checksums::write_checksums;
+use crate::cli::Cli;
+use crate::config::Config;
+use crate::dependencies::{ BUILD_ORDER_FILE, BuildOrder, Dependencies, write_build_order };
+use crate::deploy::write_index;
+use crate::diagnostics::Diagnostics;
+use crate::dialect::Dialect;
+use crate::error::{ IoContext, SplitError };
+use crate::hooks::Hooks;
+use crate::inventory::write_inventory;
+use crate::git::{ self, Committed };
+use crate::manifest::{ MANIFEST_FILE, write_manifest };
+use crate::migrations::{ OutputFormat, dbup_name, flyway_name, write_changelog };
+use crate::object::{ DatabaseObject, Headers, ObjectType, OnDuplicate, PermissionPlacement, Placement, PropertyPlacement, deploy_rank, parent_table, parse_use_statement };
+use crate::output::{ OnExisting, Output, Written };
+use crate::pipeline::ReadAhead;
+use crate::progress::progress_bar;
+use crate::sqlcmd::{ IncludeReader, Variables };
+use crate::record::ObjectRecord;
+use crate::stale::delete_stale_files;
+use crate::summary::Summary;
+use crate::tokenizer::{ Batch, Token, Tokenizer, batches };
+use crate::transform::Transforms;
+use crate::validate::Validator;
+use encoding_rs::WINDOWS_1252;
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use indicatif::ProgressBar;
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::{ HashMap, HashSet };
+use std::fs::{ self, File };
+use std::io::{ BufRead, BufReader, Read, Write };
+use std::path::{ Path, PathBuf };
+
+/// an object read from the input, along with its content
+pub struct ScannedObject {
+    pub record:        ObjectRecord,
+    /// the object's output path relative to the output root
+    pub relative_path: String,
+    pub content:       String,
+    /// where the object starts in `content`, i.e. the length of the USE
+    /// statement before it
+    pub header_start:  usize,
+}
+
+/// the file that a batch scripted after some other object is moved to
+struct Destination {
+    object_type:   String,
+    schema:        String,
+    name:          String,
+    relative_path: String,
+}
+
+/// `name` with the characters that can't be in a file name (on any platform)
+/// replaced, e.g. for service broker names like `//Example/Request`
+fn file_name(name: &str) -> Cow<'_, str> {
+    let invalid = |c: char| matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|');
+    if name.contains(invalid) {
+        Cow::Owned(name.replace(invalid, "_"))
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+/// why `object`, the last one in the input, seems to be cut off, if it does:
+/// it ends inside a string or comment, or its last batch isn't followed by GO
+/// (or whatever `dialect` ends its scripts with)
+fn truncation(object: &ScannedObject, dialect: Dialect) -> Option<&'static str> {
+    if !dialect.has_batches() {
+        return dialect.truncated(&object.content);
+    }
+    let (batches, ends_in_code) = batches(&object.content[object.header_start..]);
+    if !ends_in_code {
+        return Some("ends inside a string, quoted identifier or comment");
+    }
+    // comments after the last GO are fine
+    let last = batches.last()?;
+    let mut tokenizer = Tokenizer::new();
+    let unfinished = last.sql.split_inclusive('\n')
+        .any(|line| tokenizer.code(line).into_iter().any(|range| !line[range].trim().is_empty()));
+    if unfinished {
+        Some("has no GO after its last batch")
+    } else {
+        None
+    }
+}
+
+/// reads objects from the input, handing each one on once it's complete
+pub struct Scanner<'a> {
+    cli:         &'a Cli,
+    diagnostics: &'a Diagnostics,
+    progress:    ProgressBar,
+    /// directory that output paths are built relative to
+    root:        String,
+    /// directory that data output paths are built relative to, if data is
+    /// written apart from the rest of the output (--data-dir)
+    data_root:   Option<String>,
+    transforms:  Transforms,
+    /// sqlcmd variables, if they're substituted
+    variables:   Option<Variables>,
+    /// recognizes object headers, including --config's object types
+    headers:     Headers,
+    tokenizer:   Tokenizer,
+    /// checks that objects parse (--validate)
+    validator:   Option<Validator>,
+    /// matches what an object that belongs to a table (an index, statistic
+    /// or trigger) is ON
+    parent:      Regex,
+    /// whether any objects are written with their table
+    with_table:  bool,
+    /// matches the ALTER TABLE batches that add or check constraints, which
+    /// SSMS scripts after all tables without an object header
+    alter:       Regex,
+    /// matches the INSERT batches (and the SET IDENTITY_INSERT around them)
+    /// of scripts that include data, for the table they fill
+    data:        Regex,
+    /// matches every INSERT statement, to count the rows of data scripts
+    inserts:     Regex,
+    /// matches CREATE INDEX, for the table it's ON (--table-parts)
+    index:       Regex,
+    /// matches the sp_addextendedproperty batches SSMS scripts after objects
+    property:    Regex,
+    /// matches session options like `SET ANSI_NULLS ON`, which don't make
+    /// an object any less empty
+    set_option:  Regex,
+    /// the @level0name/@level1name arguments of sp_addextendedproperty
+    levels:      Regex,
+    /// matches GRANT, DENY and REVOKE batches
+    permission:  Regex,
+    /// what a permission statement is ON: (class::)[schema].[name], or just
+    /// (class::)[name]
+    securable:   Regex,
+    /// batches that were found after some other object, by the file they
+    /// belong in
+    detached:    Vec<ScannedObject>,
+    /// how many objects have been handed on for each output path, to find
+    /// duplicates
+    seen:        HashMap<String, usize>,
+    /// number of the next migration (--output-format flyway or dbup)
+    version:     u64,
+    current:     Option<ScannedObject>,
+    /// the tables, while objects that go with them may still follow, i.e.
+    /// until the end of the input (only if any objects are written with
+    /// their table)
+    tables:      Vec<ScannedObject>,
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(
+        cli:         &'a Cli,
+        diagnostics: &'a Diagnostics,
+        progress:    ProgressBar,
+        root:        &str,
+        data_root:   Option<&str>,
+    ) -> Result<Self, SplitError> {
+        let mut headers = match cli.split_on.as_ref() {
+            Some(pattern) => Headers::splitting_on(pattern.clone(), cli.name_group, cli.layout),
+            None          => Headers::new(Config::load(cli)?.object_types, cli.layout, cli.dialect),
+        };
+        let mut tokenizer = Tokenizer::for_dialect(cli.dialect);
+        if let Some(pattern) = cli.split_on.as_ref() {
+            tokenizer = tokenizer.with_headers(pattern.clone());
+        }
+        if let Some(pattern) = cli.header_pattern.as_ref() {
+            headers = headers.with_named_pattern(pattern.clone());
+            tokenizer = tokenizer.with_headers(pattern.clone());
+        }
+        Ok(Scanner {
+            cli,
+            diagnostics,
+            progress,
+            root:       root.to_string(),
+            data_root:  data_root.map(str::to_string),
+            transforms: Transforms::new(cli)?,
+            variables:  if cli.sqlcmd || !cli.var.is_empty() {
+                Some(Variables::new(&cli.var))
+            } else {
+                None
+            },
+            headers,
+            tokenizer,
+            validator:  if cli.validate { Some(Validator::new()) } else { None },
+            parent:     Regex::new(r"(?is)\bCREATE\s+(?:STATISTICS|TRIGGER|(?:UNIQUE\s+|(?:NON)?CLUSTERED\s+|COLUMNSTORE\s+|PRIMARY\s+|XML\s+|SPATIAL\s+)*INDEX)\s+(?:(?:\[(?:[^\]]|\]\])+\]|\w+)\.)?(?:\[(?:[^\]]|\]\])+\]|\w+)\s+ON\s+(?:\[((?:[^\]]|\]\])+)\]|(\w+))\.(?:\[((?:[^\]]|\]\])+)\]|(\w+))")
+                .expect("error compiling parent table regular expression"),
+            with_table: [cli.statistics(), cli.triggers(), cli.indexes()].contains(&Placement::WithTable),
+            alter:      Regex::new(r"(?is)^\s*ALTER\s+TABLE\s+(?:\[((?:[^\]]|\]\])+)\]|(\w+))\.(?:\[((?:[^\]]|\]\])+)\]|(\w+))\s+(?:WITH\s+(?:NO)?CHECK\s+)?(?:ADD\s+(?:CONSTRAINT|DEFAULT|FOREIGN\s+KEY|CHECK)|(?:NO)?CHECK\s+CONSTRAINT)\b")
+                .expect("error compiling ALTER TABLE constraint regular expression"),
+            data:       Regex::new(r"(?is)^\s*(?:INSERT\s+(?:INTO\s+)?|SET\s+IDENTITY_INSERT\s+)(?:\[((?:[^\]]|\]\])+)\]|(\w+))\.(?:\[((?:[^\]]|\]\])+)\]|(\w+))")
+                .expect("error compiling data regular expression"),
+            inserts:    Regex::new(r"(?im)^[ \t]*INSERT\b")
+                .expect("error compiling INSERT regular expression"),
+            index:      Regex::new(r"(?is)\bCREATE\s+(?:UNIQUE\s+|(?:NON)?CLUSTERED\s+|COLUMNSTORE\s+|PRIMARY\s+|XML\s+|SPATIAL\s+)*INDEX\s+(?:\[(?:[^\]]|\]\])+\]|\w+)\s+ON\s+(?:\[((?:[^\]]|\]\])+)\]|(\w+))\.(?:\[((?:[^\]]|\]\])+)\]|(\w+))")
+                .expect("error compiling index regular expression"),
+            property:   Regex::new(r"(?is)^\s*EXEC(?:UTE)?\s+(?:\[?sys\]?\.)?\[?sp_addextendedproperty\b")
+                .expect("error compiling extended property regular expression"),
+            set_option: Regex::new(r"(?im)^[ \t]*SET[ \t]+\w+[ \t]+(ON|OFF)[ \t]*;?[ \t]*$")
+                .expect("error compiling SET option regular expression"),
+            levels:     Regex::new(r"(?i)@level([01])name\s*=\s*N?'((?:[^']|'')*)'")
+                .expect("error compiling extended property level regular expression"),
+            permission: Regex::new(r"(?is)^\s*(?:GRANT|DENY|REVOKE)\b")
+                .expect("error compiling permission regular expression"),
+            securable:  Regex::new(r"(?is)\bON\s+(?:(\w+)\s*::\s*)?(?:\[((?:[^\]]|\]\])+)\]|(\w+))(?:\.(?:\[((?:[^\]]|\]\])+)\]|(\w+)))?")
+                .expect("error compiling securable regular expression"),
+            detached:   Vec::new(),
+            seen:       HashMap::new(),
+            version:    cli.start_version,
+            current:    None,
+            tables:     Vec::new(),
+        })
+    }
+
+    /// the folders (relative to `out_dir`) that objects may be written to
+    pub fn type_folders(&self, out_dir: &str) -> Vec<String> {
+        let mut folders = self.headers.type_folders(out_dir);
+        if self.cli.database_folders {
+            // every other folder is taken to be a database's
+            let top: HashSet<String> = folders.iter()
+                .map(|folder| folder.split('/').next().unwrap_or_default().to_string())
+                .collect();
+            let mut databases: Vec<String> = fs::read_dir(out_dir)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter(|entry| entry.path().is_dir())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .filter(|name| !name.starts_with('.') && !top.contains(name))
+                .collect();
+            databases.sort();
+            for database in databases {
+                let root = format!("{}/{}", out_dir, database);
+                folders.extend(self.headers.type_folders(&root)
+                    .into_iter()
+                    .map(|folder| format!("{}/{}", database, folder)));
+            }
+        }
+        folders
+    }
+
+    /// output path for `obj`, in `database`, i.e.
+    /// `[<database>/]<ObjectType>/[<schema>.]<name>.sql` relative to the
+    /// output root
+    fn make_path(&self, obj: &DatabaseObject, database: &str) -> String {
+        let suffix = match obj.object_type {
+            ObjectType::Table if self.cli.table_parts => ".table",
+            _                                         => "",
+        };
+        // e.g. dbo.usp_Legacy.1.sql for [dbo].[usp_Legacy];1
+        let name = match obj.number {
+            Some(number) => format!("{}.{}", obj.name, number),
+            None         => obj.name.to_owned(),
+        };
+        self.object_path(database, &obj.object_type.folder_in(self.cli.layout), &obj.schema, &name, suffix)
+    }
+
+    /// `[<database>/]<folder>/[<schema>.]<name><suffix>.sql`, with the
+    /// database folder only if objects are written by database (and no
+    /// folder at all if `folder` is empty)
+    fn object_path(&self, database: &str, folder: &str, schema: &str, name: &str, suffix: &str) -> String {
+        let mut folders: Vec<Cow<str>> = Vec::new();
+        if self.cli.database_folders && !database.is_empty() {
+            folders.push(file_name(database));
+        }
+        if !folder.is_empty() {
+            folders.push(Cow::Borrowed(folder));
+        }
+        let file = if self.cli.only_object_names || schema.is_empty() {
+            format!("{}{}.sql", file_name(name), suffix)
+        } else {
+            format!("{}.{}{}.sql", file_name(schema), file_name(name), suffix)
+        };
+        folders.push(Cow::Owned(file));
+        folders.join("/")
+    }
+
+    /// start collecting a new object; the current one (if any) is returned
+    /// since it's complete now
+    fn start_object(
+        &mut self,
+        obj:        DatabaseObject,
+        database:   &str,
+        line_start: usize,
+    ) -> Option<ScannedObject> {
+        // a database in the header wins over the last USE statement
+        let database = obj.database.to_owned().unwrap_or_else(|| database.to_string());
+        let relative_path = self.make_path(&obj, &database);
+        let path = format!("{}/{}", self.root, relative_path);
+        self.progress.set_message(format!("{} {}", obj.object_type, relative_path));
+
+        let mut record = ObjectRecord::new(
+            obj.object_type.to_string(),
+            obj.schema,
+            obj.name,
+            database,
+            path,
+            line_start);
+        record.number = obj.number;
+        self.current.replace(ScannedObject {
+            record,
+            relative_path,
+            content:      String::new(),
+            header_start: 0,
+        })
+    }
+
+    /// whether `obj` is the current object again: pg_dump scripts some
+    /// things about an object (e.g. what owns a sequence) under a header of
+    /// their own
+    fn continues(&self, obj: &DatabaseObject) -> bool {
+        match self.current.as_ref() {
+            Some(current) => !self.cli.dialect.has_batches()
+                && obj.object_type.to_string() == current.record.object_type
+                && obj.schema == current.record.schema
+                && obj.name == current.record.name,
+            None => false,
+        }
+    }
+
+    /// the end of the current object that belongs to `obj`, which starts
+    /// there, instead: mysqldump sets up the session for a trigger before
+    /// the line that names it
+    fn carried_over(&mut self, obj: &DatabaseObject) -> String {
+        if self.cli.dialect != Dialect::Mysql || !matches!(obj.object_type, ObjectType::Trigger) {
+            return String::new();
+        }
+        let Some(current) = self.current.as_mut() else {
+            return String::new();
+        };
+        let start = match current.content.rfind("/*!50003 SET @saved_cs_client") {
+            Some(start) if current.content[..start].ends_with('\n') => start,
+            _ => return String::new(),
+        };
+        let setup = current.content[start..].lines()
+            .all(|line| line.starts_with("/*!50003 SET ") || line.starts_with("DELIMITER "));
+        if !setup {
+            return String::new();
+        }
+        current.content.split_off(start)
+    }
+
+    /// add `s` to the current object; returns false if there is no current
+    /// object for it to go to
+    fn append(&mut self, s: &str, end_offset: usize) -> bool {
+        match self.current.as_mut() {
+            Some(object) => {
+                object.content.push_str(s);
+                object.record.end_offset = end_offset;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// account for (part of) a line that isn't part of any object
+    fn skip_line(&self, summary: &mut Summary, line_number: usize, text: &str) {
+        if self.cli.verbose >= 2 {
+            self.progress.suspend(|| println!(
+                "skipping line {}: {}", line_number, text.trim_end()));
+        }
+        summary.skipped_lines += 1;
+    }
+
+    /// read `reader` to the end, calling `on_object` with every object once
+    /// it's complete
+    pub fn scan<F>(
+        &mut self,
+        reader:        &mut dyn BufRead,
+        summary:       &mut Summary,
+        mut on_object: F,
+    ) -> Result<(), SplitError>
+    where
+        F: FnMut(ScannedObject, &mut Summary) -> Result<(), SplitError>,
+    {
+        let mut line = String::new();
+        let mut line_number: usize = 0;
+        let mut offset: usize = 0;
+        let mut db_use_statement = String::new();
+        let mut database = String::new();
+        // whether the last USE statement may still be followed by its GO
+        let mut use_pending = false;
+        // what comes between a USE statement and the first object after it
+        // (e.g. its SET options), with its number of batches; it goes with
+        // that object
+        let mut prelude = String::new();
+        let mut prelude_batches = 0;
+        // whether what follows is left out, up to the next header
+        let mut skipping = false;
+
+        // read lines in in_file and split into separate files
+        loop {
+            // read a line; exit if nothing left to read
+            let read = reader.read_line(&mut line)
+                .map_err(|source| SplitError::Read { line: line_number + 1, source })?;
+            if read == 0 {
+                break;
+            }
+            line_number += 1;
+            let line_start = offset;
+            offset += line.len();
+
+            if let Some(variables) = self.variables.as_mut() {
+                // directives only matter to the splitter, not the output
+                if variables.setvar(&line) {
+                    line.clear();
+                    continue;
+                }
+                if let Cow::Owned(substituted) = variables.substitute(&line) {
+                    line = substituted;
+                }
+            }
+
+            let mut token_start = line_start;
+            for token in self.tokenizer.tokens(&line) {
+                // a USE statement takes the GO that follows it along, if
+                // there is one; otherwise one is added, since e.g. CREATE
+                // PROCEDURE has to start a batch
+                if use_pending {
+                    match token {
+                        Token::Go(go, _) => {
+                            db_use_statement.push_str(go);
+                            use_pending = false;
+                            continue;
+                        },
+                        Token::Text(text) if text.trim().is_empty() => continue,
+                        _ => {
+                            let newline = if db_use_statement.ends_with("\r\n") { "\r\n" } else { "\n" };
+                            if !db_use_statement.ends_with('\n') {
+                                db_use_statement.push_str(newline);
+                            }
+                            db_use_statement.push_str("GO");
+                            db_use_statement.push_str(newline);
+                            use_pending = false;
+                        },
+                    }
+                }
+
+                match token {
+                    // keep track of which database the following objects
+                    // belong to
+                    Token::Use(text) => {
+                        // what follows is in another database, so it can't
+                        // belong to the objects before
+                        if let Some(previous) = self.current.take() {
+                            self.complete(previous, summary, &mut on_object)?;
+                        }
+                        db_use_statement = text.to_string();
+                        database = parse_use_statement(text).unwrap_or_default();
+                        use_pending = self.cli.dialect.has_batches();
+                        skipping = false;
+                    },
+                    Token::Header(text) if self.headers.is_skipped(text) => {
+                        if let Some(previous) = self.current.take() {
+                            self.complete(previous, summary, &mut on_object)?;
+                        }
+                        skipping = true;
+                        self.skip_line(summary, line_number, text);
+                    },
+                    Token::Header(text) => {
+                        skipping = false;
+                        if let Some(obj) = self.headers.parse(text) {
+                            if self.continues(&obj) {
+                                self.append(text, offset);
+                                token_start += token.text().len();
+                                continue;
+                            }
+                            if let ObjectType::Other(name) = &obj.object_type {
+                                if self.cli.strict {
+                                    return Err(SplitError::BadHeader {
+                                        line:   line_number,
+                                        text:   line.trim_end().to_string(),
+                                        reason: format!("unknown object type {}", name),
+                                    });
+                                }
+                                let message = format!("unknown object type {}, writing it to {}/",
+                                    name, obj.object_type.folder_in(self.cli.layout));
+                                if !self.cli.quiet {
+                                    self.diagnostics.warning(&message, Some((line_number, line.as_str())));
+                                }
+                                summary.warn(format!("line {}: {}", line_number, message));
+                            }
+                            let carried = self.carried_over(&obj);
+                            if let Some(previous) = self.start_object(obj, &database, token_start) {
+                                self.complete(previous, summary, &mut on_object)?;
+                            }
+                            let use_statement = self.transforms.use_statement(&db_use_statement).into_owned();
+                            self.append(&use_statement, offset);
+                            if let Some(object) = self.current.as_mut() {
+                                object.header_start = object.content.len();
+                                object.content.push_str(&carried);
+                                object.content.push_str(&prelude);
+                                object.record.batches += prelude_batches;
+                            }
+                            prelude.clear();
+                            prelude_batches = 0;
+                            self.append(text, offset);
+                        } else {
+                            if self.cli.strict {
+                                return Err(SplitError::BadHeader {
+                                    line:   line_number,
+                                    text:   line.trim_end().to_string(),
+                                    reason: String::from("unrecognized object header"),
+                                });
+                            }
+                            if !self.cli.quiet {
+                                self.diagnostics.warning(
+                                    "unrecognized object header",
+                                    Some((line_number, line.as_str())));
+                            }
+                            summary.warn(format!(
+                                "line {}: unrecognized object header: {}",
+                                line_number, text.trim_end()));
+                            summary.unrecognized_headers += 1;
+                            summary.skipped_lines += 1;
+                        }
+                    },
+                    Token::Go(text, _) | Token::Text(text) if skipping => {
+                        self.skip_line(summary, line_number, text);
+                    },
+                    Token::Go(text, count) => {
+                        if self.append(text, offset) {
+                            if let Some(object) = self.current.as_mut() {
+                                object.record.batches += count;
+                            }
+                        } else if !db_use_statement.is_empty() {
+                            prelude.push_str(text);
+                            prelude_batches += count;
+                        } else {
+                            self.skip_line(summary, line_number, text);
+                        }
+                    },
+                    Token::Text(text) => {
+                        if !self.append(text, offset) {
+                            if !db_use_statement.is_empty() {
+                                prelude.push_str(text);
+                            } else {
+                                self.skip_line(summary, line_number, text);
+                            }
+                        }
+                    },
+                }
+                token_start += token.text().len();
+            }
+            line.clear();
+        }
+
+        if let Some(last) = self.current.take() {
+            // e.g. a copy of the input that was cut off
+            if let Some(reason) = truncation(&last, self.cli.dialect) {
+                if self.cli.strict {
+                    return Err(SplitError::Truncated { path: last.relative_path, reason });
+                }
+                let message = format!("the input looks truncated: {} {}", last.relative_path, reason);
+                if !self.cli.quiet {
+                    self.diagnostics.warning(&message, None);
+                }
+                summary.warn(message);
+            }
+            self.complete(last, summary, &mut on_object)?;
+        }
+        // no object followed
+        summary.skipped_lines += prelude.lines().count();
+        for table in std::mem::take(&mut self.tables) {
+            self.finish(table, summary, &mut on_object)?;
+        }
+        for constraints in std::mem::take(&mut self.detached) {
+            self.finish(constraints, summary, &mut on_object)?;
+        }
+        Ok(())
+    }
+
+    /// where `sql`, a batch that follows `object`'s definition, should be
+    /// written instead, if it belongs somewhere else
+    fn destination(&self, object: &ScannedObject, sql: &str) -> Option<Destination> {
+        let is_object = |schema: &str, name: &str| object.record.schema == schema && object.record.name == name;
+        let is_table = |schema: &str, name: &str| object.record.object_type == "Table" && is_object(schema, name);
+        // what database-level statements are written as
+        let database = match object.record.database.as_str() {
+            ""       => String::from("Database"),
+            database => database.to_string(),
+        };
+
+        if let Some((schema, name)) = parent_table(&self.data, sql) {
+            return Some(Destination {
+                object_type:   ObjectType::Data.to_string(),
+                relative_path: self.object_path(&object.record.database, &ObjectType::Data.folder_in(self.cli.layout), &schema, &name, ""),
+                schema,
+                name,
+            });
+        }
+        if let Some((schema, name)) = parent_table(&self.alter, sql) {
+            // a table's own constraints stay where they are, unless tables
+            // are written in parts
+            if is_table(&schema, &name) && !self.cli.table_parts {
+                return None;
+            }
+            return Some(self.table_part("Constraints", &object.record.database, schema, name, ".constraints"));
+        }
+        if self.cli.table_parts {
+            if let Some((schema, name)) = parent_table(&self.index, sql) {
+                if is_table(&schema, &name) {
+                    return Some(self.table_part("Indexes", &object.record.database, schema, name, ".indexes"));
+                }
+            }
+        }
+
+        if self.property.is_match(sql) {
+            // the object the property is on: [level0].[level1], the level0
+            // schema or, without either, the database
+            let mut levels: [Option<String>; 2] = [None, None];
+            for caps in self.levels.captures_iter(sql) {
+                let level = if &caps[1] == "0" { 0 } else { 1 };
+                levels[level] = Some(caps[2].replace("''", "'"));
+            }
+            let (schema, name) = match levels {
+                [Some(schema), Some(name)] => (schema, name),
+                [Some(name), _]            => (String::new(), name),
+                _                          => (String::new(), database),
+            };
+            if self.cli.extended_properties == PropertyPlacement::WithObject && is_object(&schema, &name) {
+                return None;
+            }
+            return Some(Destination {
+                object_type:   ObjectType::ExtendedProperty.to_string(),
+                relative_path: self.object_path(&object.record.database, &ObjectType::ExtendedProperty.folder_in(self.cli.layout), &schema, &name, ""),
+                schema,
+                name,
+            });
+        }
+        if self.cli.permissions == PermissionPlacement::Separate && self.permission.is_match(sql) {
+            // database permissions (e.g. GRANT CONNECT) aren't ON anything
+            let (schema, name) = match self.securable.captures(sql) {
+                Some(caps) => {
+                    let first = caps.get(2).or(caps.get(3)).unwrap().as_str().to_string();
+                    match caps.get(4).or(caps.get(5)) {
+                        Some(name) => (first, name.as_str().to_string()),
+                        None       => (String::new(), first),
+                    }
+                },
+                None => (String::new(), database),
+            };
+            return Some(Destination {
+                object_type:   ObjectType::Permission.to_string(),
+                relative_path: self.object_path(&object.record.database, &ObjectType::Permission.folder_in(self.cli.layout), &schema, &name, ""),
+                schema,
+                name,
+            });
+        }
+        None
+    }
+
+    /// the `<schema>.<name><suffix>.sql` file in the Table folder (of
+    /// `database`), for the parts of a table other than its definition
+    fn table_part(&self, object_type: &str, database: &str, schema: String, name: String, suffix: &str) -> Destination {
+        Destination {
+            object_type:   object_type.to_string(),
+            relative_path: self.object_path(database, &ObjectType::Table.folder_in(self.cli.layout), &schema, &name, suffix),
+            schema,
+            name,
+        }
+    }
+
+    /// the file that what's moved to `destination` is collected in; it's
+    /// started (with `object`'s USE statement) if this is the first
+    fn detached_file(&mut self, destination: Destination, object: &ScannedObject) -> &mut ScannedObject {
+        let position = self.detached.iter()
+            .position(|detached| detached.relative_path == destination.relative_path
+                && detached.record.database == object.record.database);
+        match position {
+            Some(position) => &mut self.detached[position],
+            None           => {
+                let use_statement = &object.content[..object.header_start];
+                let root = match self.data_root.as_ref() {
+                    Some(data_root) if destination.object_type == ObjectType::Data.to_string() => data_root,
+                    _                                                                        => &self.root,
+                };
+                let record = ObjectRecord::new(
+                    destination.object_type,
+                    destination.schema,
+                    destination.name,
+                    object.record.database.to_owned(),
+                    format!("{}/{}", root, destination.relative_path),
+                    object.record.start_offset);
+                self.detached.push(ScannedObject {
+                    record,
+                    relative_path: destination.relative_path,
+                    content:       use_statement.to_string(),
+                    header_start:  use_statement.len(),
+                });
+                self.detached.last_mut().unwrap()
+            },
+        }
+    }
+
+    /// move the batches of `object` that belong somewhere else, like
+    /// constraints on some other table, into the files they belong in
+    fn detach(&mut self, object: &mut ScannedObject) {
+        let (batches, _) = batches(&object.content[object.header_start..]);
+        let use_statement = object.content[..object.header_start].to_string();
+        let mut content = use_statement.clone();
+        let mut moved = false;
+        // the first batch is the object's own definition
+        for (index, batch) in batches.into_iter().enumerate() {
+            let destination = match index {
+                0 => None,
+                _ => self.destination(object, &batch.sql),
+            };
+            let destination = match destination {
+                Some(destination) => destination,
+                None              => {
+                    content.push_str(&batch.sql);
+                    content.push_str(&batch.separator);
+                    continue;
+                },
+            };
+
+            moved = true;
+            if self.cli.no_data && destination.object_type == ObjectType::Data.to_string() {
+                if !batch.separator.is_empty() {
+                    object.record.batches = object.record.batches.saturating_sub(1);
+                }
+                continue;
+            }
+            let detached = self.detached_file(destination, object);
+            detached.content.push_str(batch.sql.trim_start_matches(['\r', '\n']));
+            detached.content.push_str(&batch.separator);
+            detached.record.end_offset = object.record.end_offset;
+            if !batch.separator.is_empty() {
+                detached.record.batches += 1;
+                object.record.batches = object.record.batches.saturating_sub(1);
+            }
+        }
+        if moved {
+            object.content = content;
+        }
+    }
+
+    /// transform `object`'s content, finalize its record and hand it on
+    fn complete<F>(
+        &mut self,
+        object:    ScannedObject,
+        summary:   &mut Summary,
+        on_object: &mut F,
+    ) -> Result<(), SplitError>
+    where
+        F: FnMut(ScannedObject, &mut Summary) -> Result<(), SplitError>,
+    {
+        let mut object = object;
+        if self.cli.no_data && object.record.object_type == ObjectType::Data.to_string() {
+            // data with a header of its own, e.g. from pg_dump or SQLite
+            return Ok(());
+        }
+        if !self.cli.dialect.has_batches() && self.cli.dialect != Dialect::Pattern {
+            // the comment lines above the next object's header (e.g. "--",
+            // or mysqldump's "-- Dumping routines for database ..."), but
+            // not the object's own header
+            let header_end = object.content[object.header_start..].find('\n')
+                .map_or(object.content.len(), |end| object.header_start + end + 1);
+            let newline = if object.content.ends_with("\r\n") { "\r\n" } else { "\n" };
+            let mut end = object.content.trim_end().len();
+            while let Some(start) = object.content[..end].rfind('\n').map(|at| at + 1) {
+                if start < header_end || !object.content[start..end].starts_with("--") {
+                    break;
+                }
+                end = object.content[..start].trim_end().len();
+            }
+            if end < object.content.trim_end().len() {
+                object.content.truncate(end);
+                object.content.push_str(newline);
+            }
+        }
+        if self.cli.dialect.has_batches() {
+            self.detach(&mut object);
+        }
+        // SSMS's index and statistic headers only name the index, so that
+        // ones with the same name on different tables would share a file
+        if matches!(object.record.object_type.as_str(), "Index" | "Statistic") && object.record.schema.is_empty() {
+            if let Some((schema, table)) = parent_table(&self.parent, &object.content[object.header_start..]) {
+                self.name_after_table(&mut object, &schema, &table);
+            }
+        }
+        if self.cli.table_parts && object.record.object_type == "Index" {
+            if let Some((schema, name)) = parent_table(&self.index, &object.content) {
+                let destination = self.table_part("Indexes", &object.record.database, schema, name, ".indexes");
+                let indexes = self.detached_file(destination, &object);
+                indexes.content.push_str(&object.content[object.header_start..]);
+                indexes.record.end_offset = object.record.end_offset;
+                indexes.record.batches += object.record.batches;
+                return Ok(());
+            }
+        }
+        if !self.with_table {
+            return self.finish(object, summary, on_object);
+        }
+
+        // objects that go with their table are added to it, wherever it is in
+        // the input (in the same database)
+        let placement = match object.record.object_type.as_str() {
+            "Statistic" => self.cli.statistics(),
+            "Trigger"   => self.cli.triggers(),
+            "Index"     => self.cli.indexes(),
+            _           => Placement::Folder,
+        };
+        if placement == Placement::WithTable {
+            if let Some((schema, name)) = parent_table(&self.parent, &object.content) {
+                let table = self.tables.iter_mut().find(|table| table.record.database == object.record.database
+                    && table.record.schema.eq_ignore_ascii_case(&schema)
+                    && table.record.name.eq_ignore_ascii_case(&name));
+                if let Some(table) = table {
+                    table.content.push_str(&object.content[object.header_start..]);
+                    table.record.end_offset = object.record.end_offset;
+                    table.record.batches += object.record.batches;
+                    return Ok(());
+                }
+            }
+        }
+
+        // held on to until it's clear what else goes with it
+        if object.record.object_type == "Table" {
+            self.tables.push(object);
+            return Ok(());
+        }
+        self.finish(object, summary, on_object)
+    }
+
+    /// name the file of `object`, an index or statistic, after the table it's
+    /// on as well, e.g. `Index/dbo.Orders.IX_CreatedAt.sql`
+    fn name_after_table(&self, object: &mut ScannedObject, schema: &str, table: &str) {
+        let file = if self.cli.only_object_names {
+            format!("{}.{}.sql", file_name(table), file_name(&object.record.name))
+        } else {
+            format!("{}.{}.{}.sql", file_name(schema), file_name(table), file_name(&object.record.name))
+        };
+        let folder_end = object.relative_path.rfind('/').map_or(0, |slash| slash + 1);
+        let path_folder_end = object.record.path.len() - object.relative_path.len() + folder_end;
+        object.relative_path.replace_range(folder_end.., &file);
+        object.record.path.replace_range(path_folder_end.., &file);
+    }
+
+    /// give `object` the file name that the output format calls for, in the
+    /// output root (or its database's folder)
+    fn rename(&mut self, object: &mut ScannedObject) {
+        let name = match self.cli.output_format {
+            OutputFormat::Folders
+            | OutputFormat::Liquibase => return,
+            OutputFormat::Flyway  => {
+                self.version += 1;
+                flyway_name(self.version - 1, &object.record.object_type, &object.relative_path)
+            },
+            OutputFormat::Dbup    => {
+                self.version += 1;
+                let rank = deploy_rank(&object.record.object_type);
+                dbup_name(rank, self.version - 1, &object.record.object_type, &object.relative_path)
+            },
+        };
+        let root = &object.record.path[..object.record.path.len() - object.relative_path.len()];
+        object.relative_path = if self.cli.database_folders && !object.record.database.is_empty() {
+            format!("{}/{}", file_name(&object.record.database), name)
+        } else {
+            name
+        };
+        object.record.path = format!("{}{}", root, object.relative_path);
+    }
+
+    /// whether `object` has nothing but comments (like its header) and SET
+    /// options, e.g. because its header was followed right away by the next
+    fn is_empty(&self, object: &ScannedObject) -> bool {
+        let content = &object.content[object.header_start..];
+        let mut tokenizer = Tokenizer::new();
+        let mut code = String::new();
+        for line in content.split_inclusive('\n') {
+            for range in tokenizer.code(line) {
+                code.push_str(&line[range]);
+            }
+            code.push('\n');
+        }
+        let (batches, _) = batches(&code);
+        batches.iter().all(|batch| self.set_option.replace_all(&batch.sql, "").trim().is_empty())
+    }
+
+    /// transform `object` and hand it on
+    fn finish<F>(
+        &mut self,
+        mut object: ScannedObject,
+        summary:    &mut Summary,
+        on_object:  &mut F,
+    ) -> Result<(), SplitError>
+    where
+        F: FnMut(ScannedObject, &mut Summary) -> Result<(), SplitError>,
+    {
+        if self.is_empty(&object) {
+            let message = if self.cli.skip_empty {
+                format!("{} is empty, not writing it", object.relative_path)
+            } else {
+                format!("{} is empty", object.relative_path)
+            };
+            if !self.cli.quiet {
+                self.diagnostics.warning(&message, None);
+            }
+            summary.warn(message);
+            summary.empty_objects += 1;
+            if self.cli.skip_empty {
+                summary.objects_skipped += 1;
+                return Ok(());
+            }
+        }
+
+        let occurrences = self.seen.entry(object.relative_path.to_owned()).or_insert(0);
+        *occurrences += 1;
+        if *occurrences > 1 {
+            let message = match self.cli.on_duplicate {
+                OnDuplicate::Error     => return Err(SplitError::Duplicate(object.relative_path)),
+                OnDuplicate::KeepFirst => format!("{} appears more than once in the input, keeping the first one", object.relative_path),
+                OnDuplicate::KeepLast  => format!("{} appears more than once in the input, keeping the last one", object.relative_path),
+                OnDuplicate::Both      => {
+                    // e.g. dbo.Orders.dup2.sql for the second dbo.Orders
+                    let dup_path = |path: &str| format!("{}.dup{}.sql", path.strip_suffix(".sql").unwrap_or(path), occurrences);
+                    let message = format!("{} appears more than once in the input, writing it to {} as well",
+                        object.relative_path, dup_path(&object.relative_path));
+                    object.record.path = dup_path(&object.record.path);
+                    object.relative_path = dup_path(&object.relative_path);
+                    message
+                },
+            };
+            if !self.cli.quiet {
+                self.diagnostics.warning(&message, None);
+            }
+            summary.warn(message);
+            summary.duplicate_objects += 1;
+            if self.cli.on_duplicate == OnDuplicate::KeepFirst {
+                summary.objects_skipped += 1;
+                return Ok(());
+            }
+        }
+
+        let use_statement = object.content[..object.header_start].to_string();
+        object.content = self.transforms.apply(&object.record, object.content, object.header_start);
+        if let Some(validator) = self.validator.as_ref() {
+            if let Some(error) = validator.validate(&object.content) {
+                let message = format!("{} is invalid: {}", object.relative_path, error);
+                if !self.cli.quiet {
+                    self.diagnostics.warning(&message, None);
+                }
+                summary.warn(message);
+                summary.invalid_objects += 1;
+            }
+        }
+
+        let rows_limit = match self.cli.data_chunk_rows {
+            Some(limit) if object.record.object_type == ObjectType::Data.to_string() => Some(limit),
+            _                                                                      => None,
+        };
+        let parts = if rows_limit.is_some() || self.cli.max_file_size.is_some() {
+            // rows and bytes in the current part
+            let (mut rows, mut bytes) = (0, 0);
+            into_parts(object, &use_statement, |batch| {
+                let n = match rows_limit {
+                    Some(_) => self.inserts.find_iter(&batch.sql).count(),
+                    None    => 0,
+                };
+                let size = batch.sql.len() + batch.separator.len();
+                let full = rows_limit.is_some_and(|limit| rows > 0 && rows + n > limit)
+                    || self.cli.max_file_size.is_some_and(|limit| bytes > 0 && bytes + size > limit);
+                if full {
+                    rows = n;
+                    bytes = use_statement.len() + size;
+                } else {
+                    rows += n;
+                    bytes += size;
+                }
+                full
+            })
+        } else {
+            vec![object]
+        };
+        for mut part in parts {
+            self.rename(&mut part);
+            part.record.add(part.content.as_bytes());
+            part.record.finish();
+            on_object(part, summary)?;
+        }
+        Ok(())
+    }
+}
+
+/// `object` split at its GO separators into `<name>.part<N>.sql` files,
+/// starting a new part (with `use_statement`) before every batch that `full`
+/// says doesn't fit in the current one; just `object` if it all fits in one
+fn into_parts<F>(object: ScannedObject, use_statement: &str, mut full: F) -> Vec<ScannedObject>
+where
+    F: FnMut(&Batch) -> bool,
+{
+    // content and number of batches of every part
+    let mut parts: Vec<(String, usize)> = vec![(String::new(), 0)];
+    for batch in batches(&object.content).0 {
+        if !batch.sql.trim().is_empty() && full(&batch) {
+            parts.push((use_statement.to_string(), 0));
+        }
+        let (content, count) = parts.last_mut().unwrap();
+        content.push_str(&batch.sql);
+        content.push_str(&batch.separator);
+        if !batch.separator.is_empty() {
+            *count += 1;
+        }
+    }
+    if parts.len() == 1 {
+        return vec![object];
+    }
+
+    let part_path = |path: &str, n: usize| format!("{}.part{}.sql", path.strip_suffix(".sql").unwrap_or(path), n);
+    let ScannedObject { record, relative_path, .. } = object;
+    parts.into_iter()
+        .enumerate()
+        .map(|(index, (content, count))| {
+            let mut part = ObjectRecord::new(
+                record.object_type.to_owned(),
+                record.schema.to_owned(),
+                record.name.to_owned(),
+                record.database.to_owned(),
+                part_path(&record.path, index + 1),
+                record.start_offset);
+            part.number = record.number;
+            part.end_offset = record.end_offset;
+            part.batches = count;
+            ScannedObject {
+                record:        part,
+                relative_path: part_path(&relative_path, index + 1),
+                header_start:  use_statement.len(),
+                content,
+            }
+        })
+        .collect()
+}
+
+/// writes scanned objects to the output
+struct ObjectWriter<'a> {
+    cli:          &'a Cli,
+    diagnostics:  &'a Diagnostics,
+    progress:     ProgressBar,
+    output:       Output,
+    /// where data scripts are written, if not to `output` (--data-dir)
+    data_output:  Option<Output>,
+    records:      Vec<ObjectRecord>,
+    /// paths of the files that were (re)written
+    created:      Vec<String>,
+    /// run for every file once it's in place
+    hooks:        Option<Hooks>,
+    /// every object's path relative to the output root, whether or not it
+    /// could be written
+    object_paths: HashSet<String>,
+    /// what the objects reference (--build-order)
+    dependencies: Option<Dependencies>,
+    /// the files in the last commit (--changed-only)
+    committed:    Option<Committed>,
+}
+
+impl<'a> ObjectWriter<'a> {
+    fn write(&mut self, object: ScannedObject, summary: &mut Summary) -> Result<(), SplitError> {
+        let ScannedObject { record, relative_path, content, .. } = object;
+        // data written elsewhere leaves nothing in the output directory
+        let in_output = self.data_output.is_none() || record.object_type != ObjectType::Data.to_string();
+        let mut replaces = false;
+        if in_output {
+            replaces = !self.object_paths.insert(relative_path.to_owned());
+        }
+        // a later duplicate of an object (--on-duplicate keep-last) takes
+        // the place of the one before
+        if replaces {
+            if let Some(position) = self.records.iter().position(|r| r.path == record.path) {
+                let previous = self.records.remove(position);
+                summary.forget_object(&previous.object_type);
+            }
+        }
+
+        // (--changed-only) files that match the last commit aren't written
+        let committed = in_output && !replaces && self.committed.as_ref()
+            .is_some_and(|committed| committed.unchanged(&relative_path, content.as_bytes()));
+        let on_existing = if replaces { OnExisting::Overwrite } else { self.cli.on_existing() };
+        let written = if committed {
+            Ok(Written::Unchanged)
+        } else {
+            self.output(&record.object_type).write_file(&record.path, content.as_bytes(), on_existing)
+        };
+        match written {
+            Ok(Written::Created) => {
+                if self.cli.verbose >= 1 {
+                    self.progress.suspend(|| println!("creating {:?}", record.path));
+                }
+                summary.files_created += 1;
+                summary.bytes_written += record.bytes;
+                self.created.push(record.path.to_owned());
+                // without staging, files are in place as soon as they're written
+                if self.cli.no_atomic {
+                    if let Some(hooks) = self.hooks.as_mut() {
+                        hooks.run(&record.path);
+                    }
+                }
+            },
+            Ok(Written::Unchanged) => {
+                if self.cli.verbose >= 1 {
+                    self.progress.suspend(|| println!("unchanged {:?}", record.path));
+                }
+                summary.files_unchanged += 1;
+            },
+            Ok(Written::Skipped) => {
+                // someone else's file, so it's not one of ours
+                if self.cli.verbose >= 1 {
+                    self.progress.suspend(|| println!("skipping existing {:?}", record.path));
+                }
+                summary.objects_skipped += 1;
+                return Ok(());
+            },
+            Err(e @ SplitError::OutputExists(_)) => return Err(e),
+            Err(e) => return self.object_failed(e, summary, &record.object_type),
+        }
+
+        summary.record_object(record.object_type.to_owned());
+        if let Some(dependencies) = self.dependencies.as_mut() {
+            if in_output {
+                dependencies.add(&record, &relative_path, &content);
+            }
+        }
+        if let Some(format) = self.cli.events {
+            record.emit(format)?;
+        }
+        self.records.push(record);
+        Ok(())
+    }
+
+    /// the output that objects of `object_type` are written to
+    fn output(&mut self, object_type: &str) -> &mut Output {
+        match self.data_output.as_mut() {
+            Some(data_output) if object_type == ObjectType::Data.to_string() => data_output,
+            _                                                               => &mut self.output,
+        }
+    }
+
+    /// a failure writing a single object: report it and carry on with the
+    /// next object if the output allows it
+    fn object_failed(&mut self, error: SplitError, summary: &mut Summary, object_type: &str) -> Result<(), SplitError> {
+        let output = self.output(object_type);
+        if !output.can_recover() {
+            return Err(error);
+        }
+        output.abandon_file();
+        self.diagnostics.error(&error.to_string(), None);
+        summary.warn(error.to_string());
+        summary.write_failures += 1;
+        Ok(())
+    }
+}
+
+/// a reader for `read`, decoding it from windows-1252 if requested
+pub fn decoded<R: Read + Send + 'static>(read: R, windows_1252: bool) -> Box<dyn BufRead + Send> {
+    if windows_1252 {
+        Box::new(BufReader::new(DecodeReaderBytesBuilder::new()
+            .encoding(Some(WINDOWS_1252))
+            .build(read)))
+    } else {
+        Box::new(BufReader::new(read))
+    }
+}
+
+/// open the input (a file, or stdin), decoding it and expanding includes if
+/// necessary, on a thread of its own; progress is tracked on the returned
+/// progress bar
+pub fn open_input(cli: &Cli) -> Result<(Box<dyn BufRead>, ProgressBar), SplitError> {
+    let quiet = cli.quiet;
+    let (reader, progress, base_dir) = if let Some(in_file) = cli.in_file.as_ref() {
+        // check if file exists
+        if !Path::new(&in_file).exists() {
+            return Err(SplitError::InputNotFound(in_file.to_owned()));
+        }
+        let file = File::open(in_file)
+            .map_err(|source| SplitError::OpenInput { path: in_file.to_owned(), source })?;
+        let len = file.metadata().ok().map(|m| m.len());
+        let progress = progress_bar(len, !quiet);
+        let reader = decoded(progress.wrap_read(file), cli.windows_1252);
+        let base_dir = Path::new(in_file).parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        (reader, progress, base_dir)
+    } else {
+        let stdin = std::io::stdin();
+        let progress = progress_bar(None, !quiet);
+        let reader = decoded(progress.wrap_read(stdin), cli.windows_1252);
+        (reader, progress, PathBuf::new())
+    };
+    if cli.expand_includes {
+        let reader = IncludeReader::new(reader, &base_dir, cli.windows_1252);
+        Ok((Box::new(ReadAhead::new(Box::new(reader))), progress))
+    } else {
+        Ok((Box::new(ReadAhead::new(reader)), progress))
+    }
+}
+
+/// how many --exec commands may run at the same time
+fn exec_jobs(cli: &Cli) -> usize {
+    cli.exec_jobs.unwrap_or_else(|| {
+        std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+    })
+}
+
+/// `out_dir` without a trailing slash
+pub fn trim_out_dir(out_dir: &str) -> String {
+    let mut out_dir: String  = out_dir.to_owned();
+    if !out_dir.is_empty() {
+        // if out_dir was given and ends in a slash, remove the slash
+        match out_dir.chars().last() {
+            Some('/')  => { out_dir.truncate(out_dir.len() - 1) },
+            Some('\\') => { out_dir.truncate(out_dir.len() - 1) },
+            _          => (),
+        };
+    }
+    out_dir
+}
+
+/// the files in the output (as their paths relative to its root, and their
+/// records) in deployment order: by dependency if that was analyzed, and
+/// otherwise by type
+fn deployment<'r>(
+    records:       &'r [ObjectRecord],
+    output:        &Output,
+    separate_data: bool,
+    order:         Option<&BuildOrder>,
+) -> Vec<(String, &'r ObjectRecord)> {
+    // data written elsewhere isn't part of the output
+    let mut files: Vec<(String, &ObjectRecord)> = records.iter()
+        .filter(|record| !separate_data || record.object_type != ObjectType::Data.to_string())
+        .map(|record| (output.relative_path(&record.path).to_string(), record))
+        .collect();
+    match order {
+        Some(order) => {
+            let positions: HashMap<&str, usize> = order.paths.iter()
+                .enumerate()
+                .map(|(position, path)| (*path, position))
+                .collect();
+            files.sort_by_key(|(path, _)| positions.get(path.as_str()).copied().unwrap_or(usize::MAX));
+        },
+        None => files.sort_by_key(|(_, record)| deploy_rank(&record.object_type)),
+    }
+    files
+}
+
+/// split the input described by `cli` into separate files
+pub fn split(cli: &Cli, source: &str, diagnostics: &mut Diagnostics) -> Result<(), SplitError> {
+    let out_dir = trim_out_dir(&cli.out_dir);
+
+    let mut zip_path: Option<PathBuf> = None;
+    if let Some(zp) = cli.zip.as_ref() {
+        // ensure that zp does not exist
+        if Path::new(zp).exists() {
+            return Err(SplitError::OutputExists(zp.to_owned()));
+        }
+        zip_path = if !zp.ends_with(".zip") {
+            Some(Path::new(zp).with_extension("zip"))
+        } else {
+            Some(Path::new(zp).to_path_buf())
+        }
+    }
+
+    let (mut reader, progress) = open_input(cli)?;
+    diagnostics.attach(&progress);
+    diagnostics.install_panic_hook();
+
+    // files are written either directly to out_dir or into a zip file
+    let mut output = if let Some(zp) = zip_path.as_ref() {
+        Output::zip(zp, !cli.no_atomic)?
+    } else {
+        Output::dir(&out_dir, !cli.no_atomic)?
+    };
+    // data scripts may go to a separate directory or zip file
+    let data_output = match cli.data_dir.as_ref() {
+        Some(dd) if dd.ends_with(".zip") => {
+            if Path::new(dd).exists() {
+                return Err(SplitError::OutputExists(dd.to_owned()));
+            }
+            Some(Output::zip(Path::new(dd), !cli.no_atomic)?)
+        },
+        Some(dd) => Some(Output::dir(&trim_out_dir(dd), !cli.no_atomic)?),
+        None     => None,
+    };
+    let mut summary = Summary::new();
+    let mut scanner = Scanner::new(cli, diagnostics, progress.clone(), output.root(),
+        data_output.as_ref().map(Output::root))?;
+    let type_folders = scanner.type_folders(&out_dir);
+    output.keep_unchanged(cli.incremental);
+    if cli.clean {
+        output.clean(&type_folders)?;
+    }
+    // rather than finding out once everything has been written
+    if cli.git_commit.is_some() {
+        git::require_work_tree(&out_dir)?;
+    }
+    let output_root = match zip_path.as_ref() {
+        Some(zp) => zp.display().to_string(),
+        None     => out_dir.to_owned(),
+    };
+
+    let mut writer = ObjectWriter {
+        cli,
+        diagnostics,
+        progress:     progress.clone(),
+        output,
+        data_output,
+        records:      Vec::new(),
+        created:      Vec::new(),
+        hooks:        cli.exec.as_ref().map(|command| Hooks::new(command, exec_jobs(cli))),
+        object_paths: HashSet::new(),
+        dependencies: if cli.build_order { Some(Dependencies::new()) } else { None },
+        committed:    if cli.changed_only { Some(Committed::load(&out_dir)?) } else { None },
+    };
+    scanner.scan(&mut reader, &mut summary, |object, summary| writer.write(object, summary))?;
+
+    let ObjectWriter { mut output, data_output, mut records, created, mut hooks, object_paths, dependencies, .. } = writer;
+    if cli.sorted {
+        records.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    // files written into the output root besides the objects (--git-commit)
+    let mut root_files: Vec<String> = Vec::new();
+    if cli.manifest {
+        write_manifest(&mut output, &records)?;
+        root_files.push(MANIFEST_FILE.to_string());
+    }
+    let order = dependencies.as_ref().map(Dependencies::build_order);
+    if let Some(order) = order.as_ref() {
+        if !order.cyclic.is_empty() {
+            let message = format!("{} file(s) depend on each other, so they're at the end of {} in the order they were read: {}",
+                order.cyclic.len(), BUILD_ORDER_FILE, order.cyclic.join(", "));
+            if !cli.quiet {
+                diagnostics.warning(&message, None);
+            }
+            summary.warn(message);
+        }
+        write_build_order(&mut output, order)?;
+        root_files.push(BUILD_ORDER_FILE.to_string());
+    }
+    if cli.emit_index.is_some() || cli.output_format == OutputFormat::Liquibase {
+        let files = deployment(&records, &output, data_output.is_some(), order.as_ref());
+        if let Some(name) = cli.emit_index.as_ref() {
+            let paths: Vec<String> = files.iter().map(|(path, _)| path.to_owned()).collect();
+            write_index(&mut output, name, &paths)?;
+            root_files.push(name.to_owned());
+        }
+        if cli.output_format == OutputFormat::Liquibase {
+            write_changelog(&mut output, &cli.changelog, &files)?;
+            root_files.push(cli.changelog.to_owned());
+        }
+    }
+    if let Some(algorithm) = cli.checksums {
+        write_checksums(&mut output, &records, algorithm)?;
+        root_files.push(algorithm.file_name().to_string());
+    }
+    if let Some(dest) = cli.inventory.as_ref() {
+        write_inventory(dest, &output, &records)?;
+    }
+    progress.finish_and_clear();
+    output.finish()?;
+    if let Some(data_output) = data_output {
+        data_output.finish()?;
+    }
+
+    if let Some(mut hooks) = hooks.take() {
+        if !cli.no_atomic {
+            for path in created.iter() {
+                hooks.run(path);
+            }
+        }
+        for failure in hooks.finish() {
+            diagnostics.error(&failure, None);
+            summary.warn(failure);
+            summary.hook_failures += 1;
+        }
+    }
+
+    // only once the files are in place
+    if cli.print0 {
+        let mut stdout = std::io::stdout().lock();
+        for path in created.iter() {
+            stdout.write_all(path.as_bytes())
+                .and_then(|_| stdout.write_all(b"\0"))
+                .write_context("<stdout>")?;
+        }
+        stdout.flush().write_context("<stdout>")?;
+    }
+
+    let mut deleted = Vec::new();
+    if cli.delete_stale {
+        for path in delete_stale_files(&out_dir, &type_folders, &object_paths)? {
+            if cli.verbose >= 1 {
+                println!("deleting {:?}", path);
+            }
+            summary.files_deleted += 1;
+            deleted.push(path);
+        }
+    }
+
+    if let Some(message) = cli.git_commit.as_ref() {
+        // paths relative to the output directory; data written elsewhere
+        // isn't committed
+        let in_out_dir = |path: &String| path.strip_prefix(&out_dir)
+            .and_then(|p| p.strip_prefix(['/', '\\']))
+            .map(str::to_string);
+        let mut paths: Vec<String> = created.iter().chain(deleted.iter()).filter_map(in_out_dir).collect();
+        paths.extend(root_files);
+        let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+        if !git::commit(&out_dir, &paths, message)? && !cli.quiet {
+            eprintln!("nothing to commit: no file in {} changed", out_dir);
+        }
+    }
+
+    summary.finish();
+    if !cli.quiet {
+        summary.print(output_root.as_str());
+    }
+    if let Some(dest) = cli.json.as_ref() {
+        summary.write_json(dest)?;
+    }
+
+    if summary.write_failures > 0 {
+        return Err(SplitError::WriteFailures(summary.write_failures));
+    }
+    if cli.require_objects && summary.objects_written == 0 {
+        return Err(SplitError::NoObjects(source.to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::io::Cursor;
+
+    /// the path and content of every file that splitting `input` with `args`
+    /// writes (relative to the output root), in the order they're written
+    fn split_input(args: &[&str], input: &str) -> Vec<(String, String)> {
+        let cli = Cli::try_parse_from([&["sql-splitter", "-q"], args].concat()).unwrap();
+        let diagnostics = Diagnostics::new("<test>", false);
+        let mut scanner = Scanner::new(&cli, &diagnostics, ProgressBar::hidden(), "out", None).unwrap();
+        let mut files = Vec::new();
+        scanner.scan(&mut Cursor::new(input.as_bytes()), &mut Summary::new(), |object, _| {
+            files.push((object.relative_path, object.content));
+            Ok(())
+        }).unwrap();
+        files
+    }
+
+    fn paths(files: &[(String, String)]) -> Vec<&str> {
+        files.iter().map(|(path, _)| path.as_str()).collect()
+    }
+
+    /// the paths of the files splitting `input` with `args` writes, sorted
+    fn sorted_paths(args: &[&str], input: &str) -> Vec<String> {
+        let mut paths: Vec<String> = split_input(args, input).into_iter().map(|(path, _)| path).collect();
+        paths.sort();
+        paths
+    }
+
+    /// an SSMS object header
+    fn header(object_type: &str, name: &str) -> String {
+        format!("/****** Object:  {} {}    Script Date: 1/1/2024 ******/\n", object_type, name)
+    }
+
+    #[test]
+    fn indexes_are_named_after_their_table() {
+        let input = format!("{}CREATE TABLE [dbo].[A] (CreatedAt datetime)\nGO\n{}CREATE TABLE [dbo].[B] (CreatedAt datetime)\nGO\n\
+            {}CREATE NONCLUSTERED INDEX [IX_CreatedAt] ON [dbo].[A] ([CreatedAt])\nGO\n\
+            {}CREATE NONCLUSTERED INDEX [IX_CreatedAt] ON [dbo].[B] ([CreatedAt])\nGO\n\
+            {}CREATE STATISTICS [ST_CreatedAt] ON [sales].[B]([CreatedAt])\nGO\n",
+            header("Table", "[dbo].[A]"), header("Table", "[dbo].[B]"),
+            header("Index", "[IX_CreatedAt]"), header("Index", "[IX_CreatedAt]"), header("Statistic", "[ST_CreatedAt]"));
+        assert_eq!(paths(&split_input(&[], &input)),
+            ["Table/dbo.A.sql", "Table/dbo.B.sql", "Index/dbo.A.IX_CreatedAt.sql", "Index/dbo.B.IX_CreatedAt.sql",
+             "Statistic/sales.B.ST_CreatedAt.sql"]);
+        assert_eq!(paths(&split_input(&["-n"], &input))[2..4], ["Index/A.IX_CreatedAt.sql", "Index/B.IX_CreatedAt.sql"]);
+    }
+
+    #[test]
+    fn triggers_go_with_their_table_wherever_it_is() {
+        let input = format!("{}CREATE TABLE [dbo].[A] (a int)\nGO\n{}CREATE TABLE [dbo].[B] (b int)\nGO\n\
+            {}CREATE VIEW [dbo].[V] AS SELECT a FROM [dbo].[A]\nGO\n\
+            {}CREATE TRIGGER [dbo].[trA] ON [dbo].[A] AFTER INSERT AS SELECT 1\nGO\n\
+            {}CREATE TRIGGER [dbo].[trX] ON [dbo].[X] AFTER INSERT AS SELECT 1\nGO\n",
+            header("Table", "[dbo].[A]"), header("Table", "[dbo].[B]"), header("View", "[dbo].[V]"),
+            header("Trigger", "[dbo].[trA]"), header("Trigger", "[dbo].[trX]"));
+        let files = split_input(&["--triggers", "with-table"], &input);
+        assert_eq!(paths(&files), ["View/dbo.V.sql", "Trigger/dbo.trX.sql", "Table/dbo.A.sql", "Table/dbo.B.sql"]);
+        assert!(files[2].1.contains("CREATE TABLE [dbo].[A]") && files[2].1.contains("CREATE TRIGGER [dbo].[trA]"));
+        assert!(!files[3].1.contains("CREATE TRIGGER"));
+    }
+
+    #[test]
+    fn indexes_go_with_their_table_wherever_it_is() {
+        let input = format!("{}CREATE TABLE [dbo].[A] (a int)\nGO\n{}CREATE TABLE [dbo].[B] (b int)\nGO\n\
+            {}CREATE NONCLUSTERED INDEX [IX_b] ON [dbo].[B] ([b])\nGO\n\
+            {}CREATE PROCEDURE [dbo].[p] AS SELECT 1\nGO\n\
+            {}CREATE UNIQUE CLUSTERED INDEX [IX_a] ON [dbo].[A] ([a])\nGO\n",
+            header("Table", "[dbo].[A]"), header("Table", "[dbo].[B]"), header("Index", "[IX_b]"),
+            header("StoredProcedure", "[dbo].[p]"), header("Index", "[IX_a]"));
+        let files = split_input(&["--indexes", "with-table"], &input);
+        assert_eq!(paths(&files), ["StoredProcedure/dbo.p.sql", "Table/dbo.A.sql", "Table/dbo.B.sql"]);
+        assert!(files[1].1.contains("CREATE UNIQUE CLUSTERED INDEX [IX_a] ON [dbo].[A]"));
+        assert!(files[2].1.contains("CREATE NONCLUSTERED INDEX [IX_b] ON [dbo].[B]"));
+    }
+
+    #[test]
+    fn pg_dump_dumps_are_split_at_their_headers() {
+        let input = include_str!("../tests/fixtures/pg_dump.sql");
+        assert_eq!(sorted_paths(&["--dialect", "pg-dump"], input), [
+            "Constraint/public.orders.id.sql",
+            "Constraint/public.orders.orders_pkey.sql",
+            "Data/public.orders.sql",
+            "ExtendedProperties/public.orders.sql",
+            "Index/public.ix_note.sql",
+            "Other/Extension/plpgsql.sql",
+            "Schema/sales.sql",
+            "Security/Permissions/public.orders.sql",
+            "Sequence/public.orders_id_seq.sql",
+            "Table/public.orders.sql",
+            "UserDefinedFunction/public.add(integer, integer).sql",
+            "View/sales.v_orders.sql",
+        ]);
+        let files = split_input(&["--dialect", "pg-dump"], input);
+        // what pg_dump scripts about an object right after it stays with it
+        let (_, sequence) = files.iter().find(|(path, _)| path == "Sequence/public.orders_id_seq.sql").unwrap();
+        assert!(sequence.contains("CREATE SEQUENCE public.orders_id_seq") && sequence.contains("OWNED BY public.orders.id"));
+    }
+
+    #[test]
+    fn mysqldump_dumps_are_split_at_their_comments_and_routines() {
+        let input = include_str!("../tests/fixtures/mysqldump.sql");
+        assert_eq!(sorted_paths(&["--dialect", "mysql"], input), [
+            "Data/orders.sql",
+            "StoredProcedure/p1.sql",
+            "Table/orders.sql",
+            "Trigger/orders_bi.sql",
+            "UserDefinedFunction/f1.sql",
+            "View/v_orders.sql",
+        ]);
+        let files = split_input(&["--dialect", "mysql"], input);
+        // the final view, not the stand-in mysqldump creates first
+        let (_, view) = files.iter().find(|(path, _)| path == "View/v_orders.sql").unwrap();
+        assert!(view.contains("VIEW `v_orders` AS select `orders`.`id`") && !view.contains("1 AS `id`"));
+        let (_, procedure) = files.iter().find(|(path, _)| path == "StoredProcedure/p1.sql").unwrap();
+        assert!(procedure.contains("DELIMITER ;;") && procedure.contains("SELECT 'a;b' FROM `orders`;"));
+    }
+
+    #[test]
+    fn sqlite_dumps_are_split_at_their_statements() {
+        let input = include_str!("../tests/fixtures/sqlite.sql");
+        assert_eq!(sorted_paths(&["--dialect", "sqlite"], input), [
+            "Data/order items.sql",
+            "Data/orders.sql",
+            "Data/sqlite_sequence.sql",
+            "Index/idx_note.sql",
+            "Index/ux items.sql",
+            "Table/order items.sql",
+            "Table/orders.sql",
+            "Table/t3.sql",
+            "Trigger/orders_ai.sql",
+            "Trigger/t2.sql",
+            "View/v_orders.sql",
+        ]);
+        let files = split_input(&["--dialect", "sqlite"], input);
+        // a CREATE TABLE inside a string doesn't start a table
+        let (_, data) = files.iter().find(|(path, _)| path == "Data/orders.sql").unwrap();
+        assert!(data.contains("CREATE TABLE not_a_table(x);\nline');"));
+        // and the trigger's own statements stay in it
+        let (_, trigger) = files.iter().find(|(path, _)| path == "Trigger/orders_ai.sql").unwrap();
+        assert!(trigger.contains("INSERT INTO \"order items\" VALUES(NEW.id, 0);\nEND;"));
+        assert!(files.iter().all(|(_, content)| !content.contains("COMMIT;") && !content.contains("PRAGMA")));
+    }
+
+    #[test]
+    fn sql_developer_exports_are_split_at_their_comments() {
+        let input = include_str!("../tests/fixtures/oracle.sql");
+        assert_eq!(sorted_paths(&["--dialect", "oracle"], input), [
+            "Constraint/ORDERS.foreign_keys.sql",
+            "Constraint/ORDERS.sql",
+            "Data/ORDERS.sql",
+            "Index/ORDERS_PK.sql",
+            "Other/DbLink/REMOTE.sql",
+            "Package/BILLING.sql",
+            "PackageBody/BILLING.sql",
+            "StoredProcedure/P1.sql",
+            "Table/ORDERS.sql",
+            "Trigger/ORDERS_BI.sql",
+            "UserDefinedDataType/ADDRESS_T.sql",
+        ]);
+        let files = split_input(&["--dialect", "oracle"], input);
+        // a header comment inside a string doesn't start an object
+        let (_, body) = files.iter().find(|(path, _)| path == "PackageBody/BILLING.sql").unwrap();
+        assert!(body.contains("SET note = '-- DDL for Table X'") && body.contains("END BILLING;"));
+        let (_, trigger) = files.iter().find(|(path, _)| path == "Trigger/ORDERS_BI.sql").unwrap();
+        assert!(trigger.contains("ALTER TRIGGER \"HR\".\"ORDERS_BI\" ENABLE;"));
+        assert!(files.iter().all(|(_, content)| !content.contains("File created")));
+    }
+
+    #[test]
+    fn split_on_names_files_after_the_name_group() {
+        let input = "preamble\n\
+            ## chapter 1: intro\nhello\nGO\n\
+            ## chapter 2: setup/teardown\nSELECT '\n## chapter 9: not a chapter\n'\n\
+            ## chapter 3: usage\nbye\n";
+        let files = split_input(&["--split-on", r"^## chapter (\d+): (.*)$", "--name-group", "2"], input);
+        assert_eq!(paths(&files), ["intro.sql", "setup_teardown.sql", "usage.sql"]);
+        assert_eq!(files[0].1, "## chapter 1: intro\nhello\nGO\n");
+        assert!(files[1].1.contains("## chapter 9: not a chapter"));
+        let files = split_input(&["--split-on", r"^## chapter (\d+): (.*)$"], input);
+        assert_eq!(paths(&files), ["1.sql", "2.sql", "3.sql"]);
+    }
+
+    #[test]
+    fn header_pattern_takes_the_type_schema_and_name_groups() {
+        let input = "-- object: Stored Procedure [dbo].[usp_A]\nCREATE PROCEDURE dbo.usp_A AS SELECT 1\nGO\n\
+            -- object: Table dbo.T\nCREATE TABLE dbo.T (a int)\nGO\n\
+            -- object: énum type [État]\nSELECT 1\nGO\n";
+        let pattern = r"^-- object: (?P<type>[^\[]+?) (?:(?P<schema>\[[^\]]+\]|\w+)\.)?(?P<name>\[[^\]]+\]|\w+)$";
+        assert_eq!(paths(&split_input(&["--header-pattern", pattern], input)),
+            ["StoredProcedure/dbo.usp_A.sql", "Table/dbo.T.sql", "Other/ÉnumType/État.sql"]);
+    }
+
+    #[test]
+    fn names_with_brackets_in_them_are_unescaped() {
+        let input = format!("{}CREATE TABLE [dbo].[T]]1] (a int)\nGO\n{}CREATE NONCLUSTERED INDEX [IX_a] ON [dbo].[T]]1] ([a])\nGO\n",
+            header("Table", "[dbo].[T]]1]"), header("Index", "[IX_a]"));
+        let files = split_input(&["--indexes", "with-table", "--drop-first"], &input);
+        assert_eq!(paths(&files), ["Table/dbo.T]1.sql"]);
+        assert!(files[0].1.contains("DROP TABLE IF EXISTS [dbo].[T]]1]\nGO\n"));
+        assert!(files[0].1.contains("CREATE NONCLUSTERED INDEX [IX_a] ON [dbo].[T]]1]"));
+    }
+}
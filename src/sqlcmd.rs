@@ -0,0 +1,161 @@
+/*
+ * sqlcmd features found in scripts: scripting variables (`:setvar` lines and
+ * `$(name)` references) and includes (`:r` lines)
+ */
+use crate::splitter::decoded;
+use regex::{ Captures, Regex };
+use std::borrow::Cow;
+use std::collections::{ HashMap, HashSet };
+use std::fs::File;
+use std::io::{ self, BufRead, Read };
+use std::path::{ Path, PathBuf };
+
+pub struct Variables {
+    /// values by upper-cased name, since sqlcmd variable names are
+    /// case-insensitive
+    values:    HashMap<String, String>,
+    /// variables given on the command line, which :setvar doesn't change
+    overrides: HashSet<String>,
+    setvar:    Regex,
+    reference: Regex,
+}
+
+impl Variables {
+    pub fn new(vars: &[(String, String)]) -> Self {
+        let values: HashMap<String, String> = vars.iter()
+            .map(|(name, value)| (name.to_uppercase(), value.to_owned()))
+            .collect();
+        Variables {
+            overrides: values.keys().cloned().collect(),
+            values,
+            setvar:    Regex::new(r#"^\s*:setvar\s+(\w+)(?:\s+(?:"((?:[^"]|"")*)"|(\S+)))?\s*$"#)
+                .expect("error compiling :setvar regular expression"),
+            reference: Regex::new(r"\$\((\w+)\)")
+                .expect("error compiling variable regular expression"),
+        }
+    }
+
+    /// handle `line` if it's a `:setvar` directive (a `:setvar` without a
+    /// value removes the variable); returns whether it was one
+    pub fn setvar(&mut self, line: &str) -> bool {
+        let Some(caps) = self.setvar.captures(line) else {
+            return false;
+        };
+        let name = caps[1].to_uppercase();
+        if self.overrides.contains(&name) {
+            return true;
+        }
+        let value = caps.get(2).map(|v| v.as_str().replace("\"\"", "\""))
+            .or_else(|| caps.get(3).map(|v| v.as_str().to_string()));
+        match value {
+            Some(value) => { self.values.insert(name, value); },
+            None        => { self.values.remove(&name); },
+        }
+        true
+    }
+
+    /// `line` with every reference to a known variable replaced by its
+    /// value; unknown variables are left alone
+    pub fn substitute<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        if !line.contains("$(") {
+            return Cow::Borrowed(line);
+        }
+        self.reference.replace_all(line, |caps: &Captures| {
+            match self.values.get(&caps[1].to_uppercase()) {
+                Some(value) => value.to_owned(),
+                None        => caps[0].to_string(),
+            }
+        })
+    }
+}
+
+/// a reader that replaces `:r <file>` lines with the contents of that file
+/// (resolved relative to the including file), recursively
+pub struct IncludeReader {
+    /// the input and every file currently being included, innermost last,
+    /// along with the directory includes are resolved against
+    stack:        Vec<(Box<dyn BufRead + Send>, PathBuf)>,
+    /// canonical paths of the files currently being included, to detect
+    /// cycles
+    including:    Vec<PathBuf>,
+    windows_1252: bool,
+    directive:    Regex,
+    /// the current line and how much of it has been consumed
+    line:         Vec<u8>,
+    position:     usize,
+}
+
+impl IncludeReader {
+    pub fn new(input: Box<dyn BufRead + Send>, base_dir: &Path, windows_1252: bool) -> Self {
+        IncludeReader {
+            stack:        vec![(input, base_dir.to_path_buf())],
+            including:    Vec::new(),
+            windows_1252,
+            directive:    Regex::new(r#"^\s*:r\s+(?:"([^"]+)"|(\S+))\s*$"#)
+                .expect("error compiling :r regular expression"),
+            line:         Vec::new(),
+            position:     0,
+        }
+    }
+
+    /// the file a `:r` directive in `line` refers to, if it is one
+    fn include_path(&self, line: &[u8], dir: &Path) -> Option<PathBuf> {
+        let line = std::str::from_utf8(line).ok()?;
+        let caps = self.directive.captures(line)?;
+        let file = caps.get(1).or_else(|| caps.get(2))?.as_str();
+        Some(dir.join(file))
+    }
+
+    /// start reading from the file at `path`
+    fn push(&mut self, path: PathBuf) -> io::Result<()> {
+        let error = |e: io::Error| io::Error::new(e.kind(), format!("{}: {}", path.display(), e));
+        let canonical = path.canonicalize().map_err(error)?;
+        if self.including.contains(&canonical) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                format!("{}: file includes itself", path.display())));
+        }
+        let file = File::open(&path).map_err(error)?;
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        self.stack.push((decoded(file, self.windows_1252), dir));
+        self.including.push(canonical);
+        Ok(())
+    }
+}
+
+impl Read for IncludeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for IncludeReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.position >= self.line.len() {
+            self.line.clear();
+            self.position = 0;
+            let Some((reader, dir)) = self.stack.last_mut() else {
+                break;
+            };
+            let dir = dir.to_owned();
+            if reader.read_until(b'\n', &mut self.line)? == 0 {
+                // end of an included file (or of the input)
+                self.stack.pop();
+                self.including.pop();
+                continue;
+            }
+            if let Some(path) = self.include_path(&self.line, &dir) {
+                self.line.clear();
+                self.push(path)?;
+            }
+        }
+        Ok(&self.line[self.position..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position += amount;
+    }
+}
@@ -0,0 +1,54 @@
+/*
+ * files left over from objects that are no longer in the dump
+ */
+use crate::error::{ IoContext, SplitError };
+use std::collections::HashSet;
+use std::fs;
+use std::path::{ Path, PathBuf };
+
+/// every `.sql` file directly inside one of `folders` (relative to `out_dir`)
+/// whose path relative to `out_dir` isn't in `keep`
+pub fn find_stale_files(
+    out_dir: &str,
+    folders: &[String],
+    keep:    &HashSet<String>,
+) -> Result<Vec<PathBuf>, SplitError> {
+    let mut stale = Vec::new();
+    for folder in folders {
+        let dir = Path::new(out_dir).join(folder);
+        if !dir.is_dir() {
+            continue;
+        }
+        let entries = fs::read_dir(&dir)
+            .create_context(&dir.display().to_string())?;
+        for entry in entries {
+            let entry = entry.create_context(&dir.display().to_string())?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.ends_with(".sql") || !entry.path().is_file() {
+                continue;
+            }
+            let relative = format!("{}/{}", folder, name);
+            if !keep.contains(&relative) {
+                stale.push(entry.path());
+            }
+        }
+    }
+    stale.sort();
+    Ok(stale)
+}
+
+/// delete the files found by `find_stale_files`; returns the paths of the
+/// deleted files
+pub fn delete_stale_files(
+    out_dir: &str,
+    folders: &[String],
+    keep:    &HashSet<String>,
+) -> Result<Vec<String>, SplitError> {
+    let mut deleted = Vec::new();
+    for path in find_stale_files(out_dir, folders, keep)? {
+        let display = path.display().to_string();
+        fs::remove_file(&path).write_context(&display)?;
+        deleted.push(display);
+    }
+    Ok(deleted)
+}
@@ -0,0 +1,142 @@
+/*
+ * bookkeeping for a single run, reported at the end as a table (or with
+ * --json)
+ */
+use crate::error::{ IoContext, SplitError };
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+use std::time::Instant;
+
+#[derive(Serialize)]
+pub struct Summary {
+    pub objects_written:      usize,
+    pub object_types:         BTreeMap<String, usize>,
+    pub files_created:        usize,
+    /// files left alone because their content hadn't changed (--incremental)
+    pub files_unchanged:      usize,
+    pub files_deleted:        usize,
+    pub bytes_written:        usize,
+    /// objects that were parsed but intentionally not written
+    pub objects_skipped:      usize,
+    pub unrecognized_headers: usize,
+    /// objects with the same file as one before them (see --on-duplicate)
+    pub duplicate_objects:    usize,
+    /// objects with nothing but their header (see --skip-empty)
+    pub empty_objects:        usize,
+    /// objects whose script is incomplete or doesn't parse (--validate)
+    pub invalid_objects:      usize,
+    pub write_failures:       usize,
+    /// --exec commands that failed
+    pub hook_failures:        usize,
+    pub skipped_lines:        usize,
+    pub warnings:             Vec<String>,
+    pub duration_secs:        f64,
+    #[serde(skip)]
+    started:                  Instant,
+}
+
+impl Summary {
+    pub fn new() -> Self {
+        Summary {
+            objects_written:      0,
+            object_types:         BTreeMap::new(),
+            files_created:        0,
+            files_unchanged:      0,
+            files_deleted:        0,
+            bytes_written:        0,
+            objects_skipped:      0,
+            unrecognized_headers: 0,
+            duplicate_objects:    0,
+            empty_objects:        0,
+            invalid_objects:      0,
+            write_failures:       0,
+            hook_failures:        0,
+            skipped_lines:        0,
+            warnings:             Vec::new(),
+            duration_secs:        0.0,
+            started:              Instant::now(),
+        }
+    }
+
+    pub fn record_object(&mut self, object_type: String) {
+        self.objects_written += 1;
+        *self.object_types.entry(object_type).or_insert(0) += 1;
+    }
+
+    /// undo `record_object`, for an object that was replaced by a later one
+    pub fn forget_object(&mut self, object_type: &str) {
+        self.objects_written = self.objects_written.saturating_sub(1);
+        if let Some(count) = self.object_types.get_mut(object_type) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                self.object_types.remove(object_type);
+            }
+        }
+    }
+
+    pub fn warn(&mut self, warning: String) {
+        self.warnings.push(warning);
+    }
+
+    /// stop the clock; called once splitting is done
+    pub fn finish(&mut self) {
+        self.duration_secs = self.started.elapsed().as_secs_f64();
+    }
+
+    /// print a table of objects per type followed by totals to stderr
+    pub fn print(&self, destination: &str) {
+        let total = String::from("total");
+        let type_width = self.object_types.keys()
+            .chain(std::iter::once(&total))
+            .map(|t| t.len())
+            .max()
+            .unwrap_or(0)
+            .max("object type".len());
+        let count_width = self.objects_written.to_string().len()
+            .max("count".len());
+        let rule = format!("{}  {}",
+            "-".repeat(type_width), "-".repeat(count_width));
+
+        let mut out = format!("{:<tw$}  {:>cw$}\n{}\n",
+            "object type", "count", rule,
+            tw = type_width, cw = count_width);
+        for (object_type, count) in self.object_types.iter() {
+            out.push_str(&format!("{:<tw$}  {:>cw$}\n",
+                object_type, count, tw = type_width, cw = count_width));
+        }
+        out.push_str(&format!("{}\n{:<tw$}  {:>cw$}\n\n",
+            rule, total, self.objects_written,
+            tw = type_width, cw = count_width));
+
+        out.push_str(&format!("files created:        {}\n", self.files_created));
+        out.push_str(&format!("files unchanged:      {}\n", self.files_unchanged));
+        out.push_str(&format!("files deleted:        {}\n", self.files_deleted));
+        out.push_str(&format!("bytes written:        {}\n", self.bytes_written));
+        out.push_str(&format!("objects skipped:      {}\n", self.objects_skipped));
+        out.push_str(&format!("unrecognized headers: {}\n", self.unrecognized_headers));
+        out.push_str(&format!("duplicate objects:    {}\n", self.duplicate_objects));
+        out.push_str(&format!("empty objects:        {}\n", self.empty_objects));
+        out.push_str(&format!("invalid objects:      {}\n", self.invalid_objects));
+        out.push_str(&format!("write failures:       {}\n", self.write_failures));
+        out.push_str(&format!("hook failures:        {}\n", self.hook_failures));
+        out.push_str(&format!("warnings:             {}\n", self.warnings.len()));
+        out.push_str(&format!("output:               {}\n", destination));
+        out.push_str(&format!("duration:             {:.2}s", self.duration_secs));
+        eprintln!("{}", out);
+    }
+
+    /// write the summary as JSON to `dest`, or to stdout if `dest` is "-"
+    pub fn write_json(&self, dest: &str) -> Result<(), SplitError> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|source| SplitError::Serialize { what: "summary", source })?;
+        if dest == "-" {
+            println!("{}", json);
+        } else {
+            let mut file = File::create(dest).create_context(dest)?;
+            writeln!(file, "{}", json).write_context(dest)?;
+        }
+        Ok(())
+    }
+}
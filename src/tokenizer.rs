@@ -0,0 +1,540 @@
+/*
+ * a small streaming tokenizer for the structure of SSMS-generated scripts:
+ * object headers, USE statements, GO batch separators and everything else
+ * (or, for pg_dump's and mysqldump's scripts, their headers and the lines that
+ * switch databases, for SQLite's dumps, the statements objects start with, and
+ * for Oracle SQL Developer's exports, the comments above each object).
+ *
+ * it knows just enough of T-SQL's lexical structure to track string literals,
+ * quoted identifiers and (nested) block comments across lines, so that those
+ * are only recognized where they actually are what they look like
+ */
+use crate::dialect::Dialect;
+use memchr::{ memchr, memchr2 };
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::ops::Range;
+
+// compiled once, since tokenizers are made for every object
+static GO: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)^\s*GO(?:\s+(\d+))?\s*(?:--.*)?$")
+    .expect("error compiling GO regular expression"));
+static USE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)^\s*USE\s")
+    .expect("error compiling USE regular expression"));
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Code,
+    String,
+    /// "identifier"
+    QuotedIdentifier,
+    /// [identifier]
+    BracketIdentifier,
+    /// block comments nest, so this is the depth
+    BlockComment(usize),
+}
+
+/// a piece of the input; together, the tokens of a line make up the line
+pub enum Token<'a> {
+    /// an object header comment, along with the rest of its line
+    Header(&'a str),
+    /// a line with a USE statement
+    Use(&'a str),
+    /// a batch separator line, and the number of times the batch before it
+    /// is run (e.g. `GO 5`)
+    Go(&'a str, usize),
+    /// anything else
+    Text(&'a str),
+}
+
+impl<'a> Token<'a> {
+    pub fn text(&self) -> &'a str {
+        match self {
+            Token::Header(text)
+            | Token::Use(text)
+            | Token::Go(text, _)
+            | Token::Text(text) => text,
+        }
+    }
+}
+
+pub struct Tokenizer {
+    dialect:    Dialect,
+    /// state at the end of the last line
+    state:      State,
+    /// whether the last line was in the body of a SQLite trigger, whose
+    /// statements (e.g. INSERT) don't start objects
+    in_trigger: bool,
+    /// the lines that are headers instead of the dialect's own, for
+    /// --split-on and --header-pattern
+    headers:    Option<Regex>,
+}
+
+/// a part of a line
+pub enum Span {
+    Code(Range<usize>),
+    Comment(Range<usize>),
+}
+
+/// whether `line` starts with `keyword` (in any case) after its indentation;
+/// a cheap check before running a regex on every line
+pub fn starts_with_keyword(line: &str, keyword: &str) -> bool {
+    line.trim_start().as_bytes()
+        .get(..keyword.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(keyword.as_bytes()))
+}
+
+/// the number of times the batch before `line` is run, if it's a GO line
+fn go_count(line: &str) -> Option<usize> {
+    if !starts_with_keyword(line, "GO") {
+        return None;
+    }
+    let caps = GO.captures(line.trim_end())?;
+    Some(caps.get(1)
+        .and_then(|count| count.as_str().parse().ok())
+        .unwrap_or(1))
+}
+
+/// whether `line` is a USE statement
+fn is_use(line: &str) -> bool {
+    starts_with_keyword(line, "USE") && USE.is_match(line)
+}
+
+/// whether `s` starts with an object header comment, i.e. `/****** Object:`
+pub fn is_header(s: &str) -> bool {
+    s.strip_prefix("/*")
+        .map(|rest| rest.trim_start_matches('*').trim_start().starts_with("Object:"))
+        .unwrap_or(false)
+}
+
+/// whether `line` is one of pg_dump's object headers, e.g.
+/// `-- Name: orders; Type: TABLE; Schema: public; Owner: postgres`
+pub fn is_pg_dump_header(line: &str) -> bool {
+    (line.starts_with("-- Name: ") || line.starts_with("-- Data for Name: "))
+        && line.contains("; Type: ")
+}
+
+/// whether `line` is one of mysqldump's object headers: the comments above
+/// tables, their data and views, e.g. ``-- Table structure for table `orders` ``,
+/// and the first line of routines and triggers, which have no comment
+pub fn is_mysql_header(line: &str) -> bool {
+    const COMMENTS: &[&str] = &[
+        "-- Table structure for table `",
+        "-- Dumping data for table `",
+        "-- Temporary view structure for view `",
+        "-- Temporary table structure for view `",
+        "-- Final view structure for view `",
+        "-- Current Database: `",
+    ];
+    if COMMENTS.iter().any(|comment| line.starts_with(comment)) {
+        return true;
+    }
+    // e.g. /*!50003 DROP PROCEDURE IF EXISTS `p` */;, or for a trigger
+    // /*!50003 CREATE*/ /*!50017 DEFINER=...*/ /*!50003 TRIGGER `t` ...
+    line.starts_with("/*!")
+        && (["PROCEDURE", "FUNCTION", "EVENT"].iter().any(|kind| line.contains(&format!(" DROP {} IF EXISTS `", kind)))
+            || (line.contains(" CREATE*/") && line.contains(" TRIGGER `")))
+}
+
+/// whether `line` starts one of the statements an object starts with in
+/// SQLite's dumps, which have no comments: CREATE, and the INSERTs of a
+/// table's data; the dump's own statements (PRAGMA, BEGIN TRANSACTION, ...)
+/// are headers too, of what is left out
+pub fn is_sqlite_header(line: &str) -> bool {
+    const STATEMENTS: &[&str] = &[
+        "CREATE ",
+        "INSERT INTO ",
+        "DELETE FROM ",
+        "PRAGMA ",
+        "BEGIN TRANSACTION;",
+        "ANALYZE ",
+        "COMMIT;",
+    ];
+    STATEMENTS.iter()
+        .any(|statement| line.get(..statement.len()).is_some_and(|start| start.eq_ignore_ascii_case(statement)))
+}
+
+/// whether `line` is one of the headers of Oracle SQL Developer's exports,
+/// e.g. `--  DDL for Package Body BILLING` or `REM INSERTING into HR.ORDERS`
+pub fn is_oracle_header(line: &str) -> bool {
+    const COMMENTS: &[&str] = &[
+        "DDL for ",
+        "Constraints for Table ",
+        "Ref Constraints for Table ",
+        "File created - ",
+    ];
+    match line.strip_prefix("--") {
+        Some(comment) => COMMENTS.iter().any(|start| comment.trim_start().starts_with(start)),
+        None          => line.starts_with("REM INSERTING into "),
+    }
+}
+
+impl Tokenizer {
+    pub fn new() -> Self {
+        Tokenizer::for_dialect(Dialect::Ssms)
+    }
+
+    /// a tokenizer for the scripts of `dialect`; the lexical structure is
+    /// always T-SQL's, which is close enough to find comments and strings
+    pub fn for_dialect(dialect: Dialect) -> Self {
+        Tokenizer {
+            dialect,
+            state:      State::Code,
+            in_trigger: false,
+            headers:    None,
+        }
+    }
+
+    /// this tokenizer, taking the lines that match `pattern` to be headers
+    /// instead
+    pub fn with_headers(self, pattern: Regex) -> Self {
+        Tokenizer {
+            headers: Some(pattern),
+            ..self
+        }
+    }
+
+    /// whether the input so far ends outside of any string literal, quoted
+    /// identifier or comment
+    pub fn in_code(&self) -> bool {
+        self.state == State::Code
+    }
+
+    /// whether the body of a SQLite trigger goes on after `line`, which is a
+    /// header if `header`: it starts with CREATE TRIGGER and runs up to the
+    /// END; at the end of a line
+    fn trigger_continues(&self, line: &str, header: bool) -> bool {
+        let words: Vec<String> = line.split_whitespace().take(4).map(str::to_uppercase).collect();
+        let starts = header && words.first().is_some_and(|word| word == "CREATE")
+            && words.iter().skip(1).take(2).any(|word| word == "TRIGGER");
+        let ends = line.trim_end().to_uppercase().ends_with("END;");
+        (self.in_trigger || starts) && !ends
+    }
+
+    /// split `line` into tokens
+    pub fn tokens<'a>(&mut self, line: &'a str) -> Vec<Token<'a>> {
+        if !self.dialect.is_ssms() {
+            // headers are found wherever they are, since COPY data (which
+            // isn't quoted) or backslash escapes can leave the state in a
+            // string; they're at the start of a line, in a format nothing
+            // else has. SQLite quotes the way T-SQL does, and its headers
+            // are plain statements, so they're only found in code
+            let (header, usage) = match self.dialect {
+                Dialect::PgDump => (is_pg_dump_header(line), line.starts_with("\\connect ")),
+                Dialect::Sqlite => (self.state == State::Code && !self.in_trigger && is_sqlite_header(line), false),
+                Dialect::Oracle => (is_oracle_header(line), false),
+                Dialect::Mysql  => (is_mysql_header(line), is_use(line)),
+                _               => (self.headers.as_ref()
+                    .is_some_and(|pattern| pattern.is_match(line.trim_end_matches(['\r', '\n']))), false),
+            };
+            if self.dialect == Dialect::Sqlite {
+                self.in_trigger = self.trigger_continues(line, header);
+            }
+            if header {
+                self.state = State::Code;
+                self.advance(line, &mut Vec::new());
+                return vec![Token::Header(line)];
+            }
+            if self.state == State::Code && usage {
+                self.advance(line, &mut Vec::new());
+                return vec![Token::Use(line)];
+            }
+            self.advance(line, &mut Vec::new());
+            return vec![Token::Text(line)];
+        }
+        if self.state == State::Code {
+            if let Some(count) = go_count(line) {
+                return vec![Token::Go(line, count)];
+            }
+            if is_use(line) {
+                self.advance(line, &mut Vec::new());
+                return vec![Token::Use(line)];
+            }
+        }
+        if let Some(pattern) = self.headers.as_ref() {
+            // a whole line, and only where code is
+            let header = self.state == State::Code && pattern.is_match(line.trim_end_matches(['\r', '\n']));
+            self.advance(line, &mut Vec::new());
+            return vec![if header { Token::Header(line) } else { Token::Text(line) }];
+        }
+
+        // every header runs up to the next one (or the end of the line)
+        let mut tokens = Vec::new();
+        let mut start = 0;
+        let mut in_header = false;
+        for at in self.advance(line, &mut Vec::new()) {
+            if at > start {
+                let piece = &line[start..at];
+                tokens.push(if in_header { Token::Header(piece) } else { Token::Text(piece) });
+            }
+            start = at;
+            in_header = true;
+        }
+        if start < line.len() {
+            let piece = &line[start..];
+            tokens.push(if in_header { Token::Header(piece) } else { Token::Text(piece) });
+        }
+        tokens
+    }
+
+    /// the ranges of `line` that are code, i.e. not in a string literal,
+    /// quoted identifier or comment
+    pub fn code(&mut self, line: &str) -> Vec<Range<usize>> {
+        self.spans(line).into_iter()
+            .filter_map(|span| match span {
+                Span::Code(range) => Some(range),
+                Span::Comment(_)  => None,
+            })
+            .collect()
+    }
+
+    /// the ranges of `line` that are code and those that are comments
+    /// (without the line ending); string literals and quoted identifiers are
+    /// neither
+    pub fn spans(&mut self, line: &str) -> Vec<Span> {
+        let mut spans = Vec::new();
+        self.advance(line, &mut spans);
+        spans
+    }
+
+    /// advance the state past `line`; returns the offsets of the object
+    /// headers in it, and adds its code and comment spans to `spans`
+    fn advance(&mut self, line: &str, spans: &mut Vec<Span>) -> Vec<usize> {
+        let mut headers = Vec::new();
+        let mut code_start = None;
+        let mut comment_start = match self.state {
+            State::BlockComment(_) => Some(0),
+            _                      => None,
+        };
+        let mut line_comment = None;
+        // every delimiter is ASCII, so bytes will do
+        let bytes = line.as_bytes();
+        let mut end = bytes.len();
+        let mut i = 0;
+        while i < bytes.len() {
+            // inside a string, quoted identifier or comment, only the bytes
+            // that could end it matter, so skip to the next of them
+            let rest = &bytes[i..];
+            let skip = match self.state {
+                State::Code              => Some(0),
+                State::String            => memchr(b'\'', rest),
+                State::QuotedIdentifier  => memchr(b'"', rest),
+                State::BracketIdentifier => memchr(b']', rest),
+                State::BlockComment(_)   => memchr2(b'/', b'*', rest),
+            };
+            match skip {
+                Some(skip) => i += skip,
+                None       => break,
+            }
+            let c = bytes[i];
+            let next = bytes.get(i + 1).copied();
+            let at = i;
+            let was = self.state;
+            if was == State::Code && code_start.is_none() {
+                code_start = Some(at);
+            }
+            match self.state {
+                State::Code => match (c, next) {
+                    // the rest of the line is a comment
+                    (b'-', Some(b'-')) => { end = at; line_comment = Some(at); break; },
+                    (b'/', Some(b'*')) => {
+                        if is_header(&line[i..]) {
+                            headers.push(i);
+                        }
+                        self.state = State::BlockComment(1);
+                        i += 1;
+                    },
+                    (b'\'', _)         => { self.state = State::String; },
+                    (b'"', _)          => { self.state = State::QuotedIdentifier; },
+                    (b'[', _)          => { self.state = State::BracketIdentifier; },
+                    _                  => (),
+                },
+                State::String => if c == b'\'' {
+                    // '' is an escaped quote
+                    if next == Some(b'\'') { i += 1; } else { self.state = State::Code; }
+                },
+                State::QuotedIdentifier => if c == b'"' {
+                    if next == Some(b'"') { i += 1; } else { self.state = State::Code; }
+                },
+                State::BracketIdentifier => if c == b']' {
+                    if next == Some(b']') { i += 1; } else { self.state = State::Code; }
+                },
+                State::BlockComment(depth) => match (c, next) {
+                    (b'/', Some(b'*')) => { self.state = State::BlockComment(depth + 1); i += 1; },
+                    (b'*', Some(b'/')) => {
+                        self.state = if depth == 1 { State::Code } else { State::BlockComment(depth - 1) };
+                        i += 1;
+                    },
+                    _                  => (),
+                },
+            }
+            i += 1;
+            if was == State::Code && self.state != State::Code {
+                if let Some(start) = code_start.take() {
+                    spans.push(Span::Code(start..at));
+                }
+                if matches!(self.state, State::BlockComment(_)) {
+                    comment_start = Some(at);
+                }
+            } else if matches!(was, State::BlockComment(_)) && self.state == State::Code {
+                if let Some(start) = comment_start.take() {
+                    spans.push(Span::Comment(start..i));
+                }
+            }
+        }
+        if let Some(start) = code_start {
+            spans.push(Span::Code(start..end));
+        }
+        let content_end = line.trim_end_matches(['\r', '\n']).len();
+        if let Some(start) = line_comment.or(comment_start) {
+            spans.push(Span::Comment(start..content_end.max(start)));
+        }
+        headers
+    }
+}
+
+/// a batch of a script, the (1-based) line it starts on and the GO line that
+/// ends it (empty for the last batch), along with the number of times it's
+/// run (`GO 5`)
+pub struct Batch {
+    pub line:      usize,
+    pub sql:       String,
+    pub separator: String,
+    pub count:     usize,
+}
+
+/// split `content` into its batches at the GO separators; also returns
+/// whether `content` ends in code, i.e. not in the middle of a string or
+/// comment
+pub fn batches(content: &str) -> (Vec<Batch>, bool) {
+    let mut tokenizer = Tokenizer::new();
+    let mut batches = Vec::new();
+    let mut current = Batch { line: 1, sql: String::new(), separator: String::new(), count: 1 };
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        for token in tokenizer.tokens(line) {
+            match token {
+                Token::Go(go, count) => {
+                    let next = Batch { line: index + 2, sql: String::new(), separator: String::new(), count: 1 };
+                    let mut batch = std::mem::replace(&mut current, next);
+                    batch.separator.push_str(go);
+                    batch.count = count;
+                    batches.push(batch);
+                },
+                token => current.sql.push_str(token.text()),
+            }
+        }
+    }
+    batches.push(current);
+    (batches, tokenizer.in_code())
+}
+
+/// the ranges of `content` that are code, i.e. not in a string literal,
+/// quoted identifier or comment, in order
+pub fn code_ranges(content: &str) -> Vec<Range<usize>> {
+    let mut tokenizer = Tokenizer::new();
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        ranges.extend(tokenizer.code(line).into_iter().map(|range| range.start + offset..range.end + offset));
+        offset += line.len();
+    }
+    ranges
+}
+
+/// whether `at` is in one of the `code` ranges, as from `code_ranges`
+pub fn is_in_code(code: &[Range<usize>], at: usize) -> bool {
+    let index = code.partition_point(|range| range.end <= at);
+    code.get(index).is_some_and(|range| range.start <= at)
+}
+
+/// `content` with the first match of `pattern` that starts in code replaced
+/// by `replacement` (as in `Regex::replacen`), or None if there's no such
+/// match
+pub fn replace_first_in_code(pattern: &Regex, content: &str, replacement: &str) -> Option<String> {
+    let code = code_ranges(content);
+    let caps = pattern.captures_iter(content)
+        .find(|caps| is_in_code(&code, caps.get(0).unwrap().start()))?;
+    let whole = caps.get(0).unwrap();
+    let mut replaced = String::with_capacity(content.len() + replacement.len());
+    replaced.push_str(&content[..whole.start()]);
+    caps.expand(replacement, &mut replaced);
+    replaced.push_str(&content[whole.end()..]);
+    Some(replaced)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the kind of every token of `script`, line by line: `H`eader, `U`se,
+    /// `G`o (with its count) or `T`ext
+    fn kinds(script: &str) -> Vec<String> {
+        let mut tokenizer = Tokenizer::new();
+        script.split_inclusive('\n')
+            .flat_map(|line| tokenizer.tokens(line))
+            .map(|token| match token {
+                Token::Header(_)    => String::from("H"),
+                Token::Use(_)       => String::from("U"),
+                Token::Go(_, count) => format!("G{}", count),
+                Token::Text(_)      => String::from("T"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn go_and_use_are_found_in_code() {
+        assert_eq!(kinds("USE [Sales]\nGO\nSELECT 1\ngo 5\n  Go  -- done\n"), ["U", "G1", "T", "G5", "G1"]);
+    }
+
+    #[test]
+    fn go_and_use_are_not_found_in_strings() {
+        let script = "SELECT 'first\nGO\nUSE [Other]\nlast'\nGO\n";
+        assert_eq!(kinds(script), ["T", "T", "T", "T", "G1"]);
+        // doubled quotes don't end the string
+        assert_eq!(kinds("SELECT N'it''s\nGO\n'\nGO\n"), ["T", "T", "T", "G1"]);
+    }
+
+    #[test]
+    fn go_and_use_are_not_found_in_quoted_identifiers() {
+        assert_eq!(kinds("SELECT [a\nGO\n]]b], \"c\nUSE x\"\nGO\n"), ["T", "T", "T", "T", "G1"]);
+    }
+
+    #[test]
+    fn go_and_use_are_not_found_in_block_comments() {
+        let script = "/* a\nGO\n/* nested\nUSE [Other]\n*/\nGO\n*/\nGO\n";
+        assert_eq!(kinds(script), ["T", "T", "T", "T", "T", "T", "T", "G1"]);
+    }
+
+    const HEADER: &str = "/****** Object:  StoredProcedure [dbo].[p1]    Script Date: 1/1/2024 ******/\n";
+
+    #[test]
+    fn headers_are_found_in_code() {
+        assert_eq!(kinds(&format!("{}CREATE PROCEDURE p1 AS SELECT 1\nGO\n{}", HEADER, HEADER)), ["H", "T", "G1", "H"]);
+    }
+
+    #[test]
+    fn headers_are_not_found_in_strings_comments_or_quoted_identifiers() {
+        assert_eq!(kinds(&format!("SELECT '\n{}'\n", HEADER)), ["T", "T", "T"]);
+        assert_eq!(kinds(&format!("/* /* nested */\n{}*/\n", HEADER)), ["T", "T", "T"]);
+        assert_eq!(kinds(&format!("SELECT [\n{}]\n", HEADER)), ["T", "T", "T"]);
+        assert_eq!(kinds(&format!("SELECT \"\n{}\"\n", HEADER)), ["T", "T", "T"]);
+    }
+
+    #[test]
+    fn line_comments_end_with_the_line() {
+        assert_eq!(kinds("SELECT 1 -- it's /* not a string\nGO\nUSE [Sales]\n"), ["T", "G1", "U"]);
+    }
+
+    #[test]
+    fn batches_put_the_content_back_together() {
+        let content = "CREATE PROCEDURE p AS SELECT '\nGO\n'\nGO\nSELECT 2\nGO 2\nSELECT 3";
+        let (batches, in_code) = batches(content);
+        assert!(in_code);
+        assert_eq!(batches.iter().map(|b| b.line).collect::<Vec<_>>(), [1, 5, 7]);
+        assert_eq!(batches.iter().map(|b| b.count).collect::<Vec<_>>(), [1, 2, 1]);
+        assert_eq!(batches.iter().map(|b| b.sql.as_str()).collect::<Vec<_>>(),
+            ["CREATE PROCEDURE p AS SELECT '\nGO\n'\n", "SELECT 2\n", "SELECT 3"]);
+        let joined: String = batches.iter().map(|b| format!("{}{}", b.sql, b.separator)).collect();
+        assert_eq!(joined, content);
+        assert!(!super::batches("SELECT 'unterminated\nGO\n").1);
+    }
+}
@@ -0,0 +1,452 @@
+/*
+ * changes made to an object's content before it is written
+ */
+use crate::cli::Cli;
+use crate::error::SplitError;
+use crate::format::Formatter;
+use crate::idempotent::Idempotent;
+use crate::keywords::{ KeywordCase, Keywords };
+use crate::object::{ parse_use_statement, quoted_name, rename_use_statement };
+use crate::record::ObjectRecord;
+use crate::redact::Redactor;
+use crate::tokenizer::{ Span, Tokenizer, code_ranges, is_header, is_in_code, replace_first_in_code };
+use clap::ValueEnum;
+use regex::{ Captures, Regex };
+use sha2::{ Digest, Sha256 };
+use std::borrow::Cow;
+use std::fs;
+
+/// the line endings written
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum LineEndings {
+    // whatever the input has
+    Preserve,
+    Lf,
+    Crlf,
+}
+
+/// the content changes requested on the command line
+pub struct Transforms {
+    /// prepended to every file, see `fill_placeholders`
+    header:      Option<String>,
+    /// appended to every file, see `fill_placeholders`
+    footer:      Option<String>,
+    /// matches the "Script Date: ..." part of object headers
+    script_date: Option<Regex>,
+    /// matches `SET ANSI_NULLS ON` / `SET QUOTED_IDENTIFIER ON` batches
+    set_options: Option<Regex>,
+    /// leave out the USE statement at the top of every file
+    no_use:      bool,
+    /// (old, new) database names for USE statements
+    rename_db:   Vec<(String, String)>,
+    /// matches the CREATE of a procedure, function, view or trigger
+    create:      Option<Regex>,
+    /// leave out every comment but the object header
+    no_comments: bool,
+    format:      Option<Formatter>,
+    /// masks column values in INSERT statements
+    redact:      Option<Redactor>,
+    /// matches passwords, password hashes and secrets
+    secrets:     Option<Regex>,
+    /// matches the binary literals of CLR assemblies, which are replaced by
+    /// their size and hash
+    assembly:    Option<Regex>,
+    /// drop every object before creating it
+    drop_first:  bool,
+    idempotent:  Option<Idempotent>,
+    /// puts keywords in the same case
+    keywords:    Option<Keywords>,
+    line_endings: LineEndings,
+}
+
+/// replace `{schema}`, `{name}`, `{type}` and `{database}` in `template` with
+/// the values for `record`
+fn fill_placeholders(template: &str, record: &ObjectRecord) -> String {
+    template
+        .replace("{schema}", &record.schema)
+        .replace("{name}", &record.name)
+        .replace("{type}", &record.object_type)
+        .replace("{database}", &record.database)
+}
+
+/// the keyword to use in `DROP <keyword> IF EXISTS` for `object_type`, if
+/// objects of that type can be dropped by name
+fn drop_keyword(object_type: &str) -> Option<&'static str> {
+    match object_type {
+        "Assembly"             => Some("ASSEMBLY"),
+        "DatabaseRole"         => Some("ROLE"),
+        "Default"              => Some("DEFAULT"),
+        "Rule"                 => Some("RULE"),
+        "Schema"               => Some("SCHEMA"),
+        "SecurityPolicy"       => Some("SECURITY POLICY"),
+        "Sequence"             => Some("SEQUENCE"),
+        "StoredProcedure"      => Some("PROCEDURE"),
+        "Synonym"              => Some("SYNONYM"),
+        "Table"                => Some("TABLE"),
+        "Trigger"              => Some("TRIGGER"),
+        "User"                 => Some("USER"),
+        "UserDefinedAggregate" => Some("AGGREGATE"),
+        "UserDefinedDataType"  => Some("TYPE"),
+        "UserDefinedFunction"  => Some("FUNCTION"),
+        "UserDefinedTableType" => Some("TYPE"),
+        "View"                 => Some("VIEW"),
+        // indexes and DDL triggers also need what they're on, and databases
+        // aren't dropped from a script that's run inside them
+        _                      => None,
+    }
+}
+
+/// `DROP ... IF EXISTS` batch for `record`'s object
+fn drop_statement(record: &ObjectRecord) -> Option<String> {
+    let keyword = drop_keyword(&record.object_type)?;
+    let name = quoted_name(&record.schema, &record.name);
+    Some(format!("DROP {} IF EXISTS {}\nGO\n", keyword, name))
+}
+
+/// where to insert anything that has to come before the definition in
+/// `object`, an object's script without the USE statement above it: just
+/// past its header line if it starts with an SSMS header, and otherwise at
+/// its start (e.g. above the comment headers of other dialects)
+fn after_header(object: &str) -> usize {
+    match object.split_inclusive('\n').next() {
+        Some(line) if is_header(line.trim_start()) => line.len(),
+        _                                          => 0,
+    }
+}
+
+/// `content` without its comments, except for the object header; lines that
+/// only had comments on them are left out entirely
+fn strip_comments(content: &str) -> String {
+    let mut tokenizer = Tokenizer::new();
+    let mut stripped = String::with_capacity(content.len());
+    for line in content.split_inclusive('\n') {
+        let comments: Vec<_> = tokenizer.spans(line).into_iter()
+            .filter_map(|span| match span {
+                Span::Comment(range) if !is_header(&line[range.clone()]) => Some(range),
+                _                                                         => None,
+            })
+            .collect();
+        if comments.is_empty() {
+            stripped.push_str(line);
+            continue;
+        }
+        let mut kept = String::with_capacity(line.len());
+        let mut copied = 0;
+        for range in comments {
+            kept.push_str(&line[copied..range.start]);
+            // don't glue together what the comment kept apart
+            let before = kept.chars().last().map(char::is_whitespace);
+            let after = line[range.end..].chars().next().map(char::is_whitespace);
+            if before == Some(false) && after == Some(false) {
+                kept.push(' ');
+            }
+            copied = range.end;
+        }
+        let ending = &line[line.trim_end_matches(['\r', '\n']).len()..];
+        let kept = kept + &line[copied..line.len() - ending.len()];
+        if !kept.trim().is_empty() {
+            stripped.push_str(kept.trim_end());
+            stripped.push_str(ending);
+        }
+    }
+    stripped
+}
+
+/// what's left of the binary literal `0x<hex>`: an empty literal and a
+/// comment with the size and sha256 of the binary
+fn omit_binary(hex: &str) -> String {
+    let bytes: Vec<u8> = hex.as_bytes()
+        .chunks(2)
+        .filter_map(|pair| std::str::from_utf8(pair).ok())
+        .filter_map(|pair| u8::from_str_radix(pair, 16).ok())
+        .collect();
+    let hash: String = Sha256::digest(&bytes).iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("0x /* {} bytes omitted, sha256 {} */", bytes.len(), hash)
+}
+
+/// make sure `s` ends with a newline (unless it's empty)
+fn end_line(mut s: String) -> String {
+    if !s.is_empty() && !s.ends_with('\n') {
+        s.push('\n');
+    }
+    s
+}
+
+/// read a template file, making sure it ends with a newline
+fn read_template(path: &str) -> Result<String, SplitError> {
+    let template = fs::read_to_string(path)
+        .map_err(|source| SplitError::OpenInput { path: path.to_owned(), source })?;
+    Ok(end_line(template))
+}
+
+impl Transforms {
+    pub fn new(cli: &Cli) -> Result<Self, SplitError> {
+        let header = match cli.header_file.as_ref() {
+            Some(path) => Some(read_template(path)?),
+            None       => None,
+        };
+        let footer = match (cli.footer_file.as_ref(), cli.footer_text.as_ref()) {
+            (Some(path), _)    => Some(read_template(path)?),
+            (None, Some(text)) => Some(end_line(text.to_owned())),
+            (None, None)       => None,
+        };
+        let script_date = if cli.strip_script_date {
+            Some(Regex::new(r"(?m)^(/\*+\s*Object:[^\r\n]*?)\s+Script Date:[^*\r\n]*(\*+/)")
+                .expect("error compiling Script Date regular expression"))
+        } else {
+            None
+        };
+        // OFF changes how the object behaves, so only the (default) ON is
+        // safe to remove
+        let set_options = if cli.strip_set_options {
+            Some(Regex::new(r"(?im)^SET\s+(ANSI_NULLS|QUOTED_IDENTIFIER)\s+ON\s*;?[ \t]*\r?\nGO[ \t]*(\r?\n|$)")
+                .expect("error compiling SET option regular expression"))
+        } else {
+            None
+        };
+        let create = if cli.create_or_alter {
+            Some(Regex::new(r"(?im)^([ \t]*)CREATE(\s+)(PROC|PROCEDURE|FUNCTION|VIEW|TRIGGER)\b")
+                .expect("error compiling CREATE regular expression"))
+        } else {
+            None
+        };
+        let assembly = if cli.omit_assembly_binaries {
+            Some(Regex::new(r"(?i)\b0x([0-9a-f]{64,})")
+                .expect("error compiling binary literal regular expression"))
+        } else {
+            None
+        };
+        let secrets = if cli.mask_secrets {
+            Some(Regex::new(r"(?i)\b(PASSWORD|SECRET)(\s*=\s*)(?:N?'(?:[^']|'')*'|(0x[0-9a-f]+))")
+                .expect("error compiling secret regular expression"))
+        } else {
+            None
+        };
+        let redact = match cli.redact.as_ref() {
+            Some(path) => Some(Redactor::load(path)?),
+            None       => None,
+        };
+        Ok(Transforms {
+            header,
+            footer,
+            script_date,
+            set_options,
+            no_use:    cli.no_use,
+            rename_db: cli.rename_db.to_owned(),
+            create,
+            no_comments: cli.strip_comments,
+            redact,
+            secrets,
+            assembly,
+            format:     if cli.format { Some(Formatter::new()) } else { None },
+            drop_first: cli.drop_first,
+            idempotent: if cli.idempotent { Some(Idempotent::new()) } else { None },
+            keywords:   if cli.keywords == KeywordCase::Preserve { None } else { Some(Keywords::new(cli.keywords)) },
+            line_endings: cli.line_endings,
+        })
+    }
+
+    /// the USE statement (and its GO) to put at the top of every file for
+    /// objects in the database it selects
+    pub fn use_statement<'s>(&self, statement: &'s str) -> Cow<'s, str> {
+        if self.no_use {
+            return Cow::Borrowed("");
+        }
+        if let Some(database) = parse_use_statement(statement) {
+            let renamed = self.rename_db.iter()
+                .find(|(old, _)| old.eq_ignore_ascii_case(&database));
+            if let Some(renamed) = renamed.and_then(|(_, new)| rename_use_statement(statement, new)) {
+                return Cow::Owned(renamed);
+            }
+        }
+        Cow::Borrowed(statement)
+    }
+
+    /// apply every transform to `content`, the content of `record`'s object,
+    /// whose USE statement ends at `header_start`
+    pub fn apply(&self, record: &ObjectRecord, content: String, header_start: usize) -> String {
+        // the object itself is transformed apart from its USE statement, so
+        // that nothing is inserted above it
+        let mut use_statement = content;
+        let mut content = use_statement.split_off(header_start);
+        if let Some(re) = self.script_date.as_ref() {
+            content = re.replace_all(&content, "$1 $2").into_owned();
+        }
+        if let Some(re) = self.set_options.as_ref() {
+            content = re.replace_all(&content, "").into_owned();
+        }
+        if self.no_comments {
+            content = strip_comments(&content);
+        }
+        if let Some(redactor) = self.redact.as_ref() {
+            content = redactor.apply(&content);
+        }
+        if let Some(re) = self.secrets.as_ref() {
+            // hashes stay binary literals, so e.g. `PASSWORD = ... HASHED`
+            // still parses; what only looks like a secret in a string or
+            // comment is left alone
+            let code = code_ranges(&content);
+            content = re.replace_all(&content, |caps: &Captures| match caps.get(3) {
+                _ if !is_in_code(&code, caps.get(0).unwrap().start()) => caps[0].to_string(),
+                Some(_)                                               => format!("{}{}0x /* masked */", &caps[1], &caps[2]),
+                None                                                  => format!("{}{}N'********'", &caps[1], &caps[2]),
+            }).into_owned();
+        }
+        if let Some(re) = self.assembly.as_ref() {
+            if record.object_type == "Assembly" {
+                content = re.replace_all(&content, |caps: &Captures| omit_binary(&caps[1])).into_owned();
+            }
+        }
+        if let Some(re) = self.create.as_ref() {
+            // only the object's own definition, not e.g. a CREATE inside it,
+            // or in a comment or string above it
+            if let Some(altered) = replace_first_in_code(re, &content, "${1}CREATE OR ALTER$2$3") {
+                content = altered;
+            }
+        }
+        // before anything is added around the object's own definition
+        if let Some(formatter) = self.format.as_ref() {
+            content = formatter.format(&content);
+        }
+        if self.drop_first {
+            if let Some(drop) = drop_statement(record) {
+                content.insert_str(after_header(&content), &drop);
+            }
+        }
+        if let Some(idempotent) = self.idempotent.as_ref() {
+            let at = after_header(&content);
+            content = idempotent.apply(record, content, at);
+        }
+        content.insert_str(0, &use_statement);
+        if let Some(keywords) = self.keywords.as_ref() {
+            content = keywords.apply(&content);
+        }
+        if let Some(header) = self.header.as_ref() {
+            content.insert_str(0, &fill_placeholders(header, record));
+        }
+        if let Some(footer) = self.footer.as_ref() {
+            content = end_line(content);
+            content.push_str(&fill_placeholders(footer, record));
+        }
+        // last, since e.g. templates may have their own line endings
+        match self.line_endings {
+            LineEndings::Preserve => content,
+            LineEndings::Lf       => content.replace("\r\n", "\n"),
+            LineEndings::Crlf     => content.replace("\r\n", "\n").replace('\n', "\r\n"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn transforms(args: &[&str]) -> Transforms {
+        let cli = Cli::try_parse_from([&["sql-splitter"], args].concat()).unwrap();
+        Transforms::new(&cli).unwrap()
+    }
+
+    fn procedure() -> ObjectRecord {
+        ObjectRecord::new(String::from("StoredProcedure"), String::from("dbo"), String::from("p1"),
+            String::from("Sales"), String::from("StoredProcedure/dbo.p1.sql"), 0)
+    }
+
+    const USE: &str = "USE [Sales]\nGO\n";
+
+    #[test]
+    fn after_header_skips_ssms_headers_with_any_number_of_asterisks() {
+        let object = "/**** Object:  StoredProcedure [dbo].[p1] ****/\nCREATE PROCEDURE [dbo].[p1] AS SELECT 1\n";
+        assert_eq!(after_header(object), object.find("CREATE").unwrap());
+        let object = "/****** Object:  StoredProcedure [dbo].[p1]    Script Date: 1/1/2024 ******/\r\nCREATE PROCEDURE\r\n";
+        assert_eq!(after_header(object), object.find("CREATE").unwrap());
+    }
+
+    #[test]
+    fn after_header_is_the_start_without_an_ssms_header() {
+        assert_eq!(after_header("-- Name: p1; Type: FUNCTION; Schema: public\nCREATE FUNCTION p1()\n"), 0);
+        assert_eq!(after_header("CREATE PROCEDURE [dbo].[p1] AS SELECT 1\n"), 0);
+        assert_eq!(after_header(""), 0);
+    }
+
+    #[test]
+    fn drop_first_goes_below_the_use_statement() {
+        let object = "/**** Object:  StoredProcedure [dbo].[p1] ****/\nCREATE PROCEDURE [dbo].[p1] AS SELECT 1\nGO\n";
+        let content = transforms(&["--drop-first"]).apply(&procedure(), format!("{}{}", USE, object), USE.len());
+        assert_eq!(content, "USE [Sales]\nGO\n\
+            /**** Object:  StoredProcedure [dbo].[p1] ****/\n\
+            DROP PROCEDURE IF EXISTS [dbo].[p1]\nGO\n\
+            CREATE PROCEDURE [dbo].[p1] AS SELECT 1\nGO\n");
+    }
+
+    #[test]
+    fn drop_first_goes_below_the_use_statement_without_a_header() {
+        // e.g. a header matched by --header-pattern
+        let object = "-- procedure dbo.p1\nCREATE PROCEDURE [dbo].[p1] AS SELECT 1\nGO\n";
+        let content = transforms(&["--drop-first"]).apply(&procedure(), format!("{}{}", USE, object), USE.len());
+        assert_eq!(content, format!("{}DROP PROCEDURE IF EXISTS [dbo].[p1]\nGO\n{}", USE, object));
+    }
+
+    #[test]
+    fn rename_db_replaces_only_the_database_name() {
+        let transforms = transforms(&["--rename-db", "USE=Sales", "--rename-db", "a]b=x]y"]);
+        assert_eq!(transforms.use_statement("USE [USE]\nGO\n"), "USE [Sales]\nGO\n");
+        assert_eq!(transforms.use_statement("use USE;\n"), "use Sales;\n");
+        assert_eq!(transforms.use_statement("USE [a]]b]\nGO\n"), "USE [x]]y]\nGO\n");
+        assert_eq!(transforms.use_statement("USE `a]b`;\n"), "USE `x]y`;\n");
+        assert_eq!(transforms.use_statement("USE [Other]\nGO\n"), "USE [Other]\nGO\n");
+    }
+
+    fn object(object_type: &str, name: &str) -> ObjectRecord {
+        ObjectRecord::new(String::from(object_type), String::new(), String::from(name),
+            String::from("Sales"), format!("{}/{}.sql", object_type, name), 0)
+    }
+
+    #[test]
+    fn mask_secrets_masks_passwords_hashes_and_secrets() {
+        let transforms = transforms(&["--mask-secrets"]);
+        let login = "CREATE LOGIN [app] WITH PASSWORD = N'It''s s3cret!', CHECK_POLICY = ON\nGO\n\
+            CREATE LOGIN [old] WITH PASSWORD=0x0200A1B2C3D4E5F6 HASHED, SID = 0x1234\nGO\n";
+        assert_eq!(transforms.apply(&object("Login", "app"), login.to_string(), 0),
+            "CREATE LOGIN [app] WITH PASSWORD = N'********', CHECK_POLICY = ON\nGO\n\
+            CREATE LOGIN [old] WITH PASSWORD=0x /* masked */ HASHED, SID = 0x1234\nGO\n");
+        let credential = "CREATE DATABASE SCOPED CREDENTIAL [blob] WITH IDENTITY = 'SHARED ACCESS SIGNATURE', SECRET = 'sv=2026&sig=abc'\nGO\n";
+        assert_eq!(transforms.apply(&object("DatabaseScopedCredential", "blob"), credential.to_string(), 0),
+            "CREATE DATABASE SCOPED CREDENTIAL [blob] WITH IDENTITY = 'SHARED ACCESS SIGNATURE', SECRET = N'********'\nGO\n");
+    }
+
+    #[test]
+    fn mask_secrets_leaves_other_text_alone() {
+        let script = "/* checks the password policy */\n\
+            CREATE PROCEDURE [dbo].[p1] @password nvarchar(100) AS\n\
+            UPDATE dbo.Users SET PasswordHash = HASHBYTES('SHA2_256', @password), PasswordHint = N'pet''s name'\n\
+            SELECT Secret, SecretName FROM dbo.Vault WHERE Secrets = 1 AND Note = N'password = ''x'' is weak'\nGO\n";
+        assert_eq!(transforms(&["--mask-secrets"]).apply(&procedure(), script.to_string(), 0), script);
+    }
+
+    #[test]
+    fn drop_first_escapes_brackets_in_names() {
+        let record = ObjectRecord::new(String::from("StoredProcedure"), String::from("dbo"), String::from("p]1"),
+            String::from("Sales"), String::from("StoredProcedure/dbo.p]1.sql"), 0);
+        let object = "CREATE PROCEDURE [dbo].[p]]1] AS SELECT 1\nGO\n";
+        let content = transforms(&["--drop-first"]).apply(&record, object.to_string(), 0);
+        assert_eq!(content, format!("DROP PROCEDURE IF EXISTS [dbo].[p]]1]\nGO\n{}", object));
+    }
+
+    #[test]
+    fn create_or_alter_skips_creates_in_comments_and_strings() {
+        let object = "/*\nCREATE PROCEDURE [dbo].[p_old] AS SELECT 0\n*/\n\
+            -- replaces\n\
+            --CREATE PROCEDURE [dbo].[p_old]\n\
+            PRINT N'replacing\n\
+            CREATE PROCEDURE [dbo].[p_old]'\n\
+            GO\n\
+            create   procedure [dbo].[p1] AS\n\
+            CREATE TABLE #t (a int)\n\
+            GO\n";
+        let content = transforms(&["--create-or-alter"]).apply(&procedure(), object.to_string(), 0);
+        assert_eq!(content, object.replace("create   procedure", "CREATE OR ALTER   procedure"));
+    }
+}
@@ -0,0 +1,50 @@
+/*
+ * --validate: check that every object's script parses as T-SQL, to catch
+ * truncated or corrupted definitions before they're deployed
+ */
+use crate::tokenizer::{ Batch, batches };
+use regex::Regex;
+use sqlparser::dialect::MsSqlDialect;
+use sqlparser::parser::{ Parser, ParserError };
+
+pub struct Validator {
+    dialect:     MsSqlDialect,
+    /// session options like `SET ANSI_NULLS ON`, which the parser doesn't
+    /// know about
+    set_options: Regex,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Validator {
+            dialect:     MsSqlDialect {},
+            set_options: Regex::new(r"(?im)^[ \t]*SET[ \t]+\w+[ \t]+(ON|OFF)[ \t]*;?[ \t]*$")
+                .expect("error compiling SET option regular expression"),
+        }
+    }
+
+    /// what's wrong with `content`, an object's script, if anything.
+    ///
+    /// the parser doesn't know all of T-SQL (e.g. storage options like
+    /// `ON [PRIMARY]`), so an error it runs into before the end of a batch
+    /// may well be valid T-SQL; only running out of input means a batch is
+    /// certainly incomplete
+    pub fn validate(&self, content: &str) -> Option<String> {
+        let (batches, ends_in_code) = batches(content);
+        if !ends_in_code {
+            return Some(String::from("ends inside a string, quoted identifier or comment"));
+        }
+        batches.into_iter()
+            .map(|batch| Batch {
+                // blank rather than remove, to keep the parser's line numbers
+                sql: self.set_options.replace_all(&batch.sql, "").into_owned(),
+                ..batch
+            })
+            .filter(|batch| !batch.sql.trim().is_empty())
+            .find_map(|batch| match Parser::parse_sql(&self.dialect, &batch.sql) {
+                Err(ParserError::ParserError(e)) if e.contains("found: EOF") =>
+                    Some(format!("the batch starting on line {} is incomplete: {}", batch.line, e)),
+                _ => None,
+            })
+    }
+}
@@ -0,0 +1,87 @@
+--------------------------------------------------------
+--  File created - Friday-October-16-2026   
+--------------------------------------------------------
+--------------------------------------------------------
+--  DDL for Type ADDRESS_T
+--------------------------------------------------------
+
+  CREATE OR REPLACE EDITIONABLE TYPE "HR"."ADDRESS_T" AS OBJECT (street VARCHAR2(100));
+
+/
+--------------------------------------------------------
+--  DDL for Table ORDERS
+--------------------------------------------------------
+
+  CREATE TABLE "HR"."ORDERS" 
+   (	"ID" NUMBER, 
+	"NOTE" VARCHAR2(50 BYTE)
+   ) SEGMENT CREATION IMMEDIATE 
+  TABLESPACE "USERS" ;
+REM INSERTING into HR.ORDERS
+SET DEFINE OFF;
+Insert into HR.ORDERS (ID,NOTE) values ('1','it''s');
+--------------------------------------------------------
+--  DDL for Index ORDERS_PK
+--------------------------------------------------------
+
+  CREATE UNIQUE INDEX "HR"."ORDERS_PK" ON "HR"."ORDERS" ("ID") 
+  TABLESPACE "USERS" ;
+--------------------------------------------------------
+--  DDL for Package BILLING
+--------------------------------------------------------
+
+  CREATE OR REPLACE EDITIONABLE PACKAGE "HR"."BILLING" AS
+  PROCEDURE charge(p_id NUMBER);
+END BILLING;
+
+/
+--------------------------------------------------------
+--  DDL for Package Body BILLING
+--------------------------------------------------------
+
+  CREATE OR REPLACE EDITIONABLE PACKAGE BODY "HR"."BILLING" AS
+  PROCEDURE charge(p_id NUMBER) IS
+  BEGIN
+    UPDATE orders SET note = '-- DDL for Table X' WHERE id = p_id;
+  END;
+END BILLING;
+
+/
+--------------------------------------------------------
+--  DDL for Trigger ORDERS_BI
+--------------------------------------------------------
+
+  CREATE OR REPLACE EDITIONABLE TRIGGER "HR"."ORDERS_BI" 
+BEFORE INSERT ON orders FOR EACH ROW
+BEGIN
+  :NEW.note := UPPER(:NEW.note);
+END;
+/
+ALTER TRIGGER "HR"."ORDERS_BI" ENABLE;
+--------------------------------------------------------
+--  DDL for DB Link REMOTE
+--------------------------------------------------------
+
+  CREATE DATABASE LINK "REMOTE" CONNECT TO "X" IDENTIFIED BY VALUES ':1' USING 'remote';
+--------------------------------------------------------
+--  Constraints for Table ORDERS
+--------------------------------------------------------
+
+  ALTER TABLE "HR"."ORDERS" MODIFY ("ID" NOT NULL ENABLE);
+  ALTER TABLE "HR"."ORDERS" ADD CONSTRAINT "ORDERS_PK" PRIMARY KEY ("ID") ENABLE;
+--------------------------------------------------------
+--  Ref Constraints for Table ORDERS
+--------------------------------------------------------
+
+  ALTER TABLE "HR"."ORDERS" ADD CONSTRAINT "ORDERS_FK" FOREIGN KEY ("ID") REFERENCES "HR"."X" ("ID") ENABLE;
+--------------------------------------------------------
+--  DDL for Procedure P1
+--------------------------------------------------------
+set define off;
+
+  CREATE OR REPLACE EDITIONABLE PROCEDURE "HR"."P1" AS
+BEGIN
+  NULL;
+END;
+
+/
@@ -0,0 +1,20 @@
+PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE orders(id INTEGER PRIMARY KEY AUTOINCREMENT, note TEXT);
+INSERT INTO orders VALUES(1,'a');
+INSERT INTO orders VALUES(2,'multi
+CREATE TABLE not_a_table(x);
+line');
+CREATE TABLE "order items"(order_id INT REFERENCES orders(id), qty INT);
+INSERT INTO "order items" VALUES(1,3);
+DELETE FROM sqlite_sequence;
+INSERT INTO sqlite_sequence VALUES('orders',2);
+CREATE INDEX idx_note ON orders(note);
+CREATE UNIQUE INDEX [ux items] ON "order items"(order_id);
+CREATE VIEW v_orders AS SELECT * FROM orders;
+CREATE TRIGGER orders_ai AFTER INSERT ON orders BEGIN
+INSERT INTO "order items" VALUES(NEW.id, 0);
+END;
+CREATE TRIGGER t2 AFTER DELETE ON orders BEGIN DELETE FROM orders; END;
+CREATE TABLE t3(x);
+COMMIT;